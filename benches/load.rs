@@ -0,0 +1,155 @@
+//! Load test for the UDP front-end of the server, showing how throughput
+//! scales with the number of workers.
+//!
+//! Starts `dex-server` with 1, 2, 4, ... workers (up to the number of CPUs)
+//! and measures how many queries per second a set of client threads get
+//! answered. Run with `cargo bench --bench load`.
+
+use std::{
+    fs,
+    net::{SocketAddr, UdpSocket},
+    process::{Child, Command, Stdio},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use dex::{Bytes, Message, Name, Question, QuestionClass, QuestionType};
+
+/// Time to send queries for at each number of workers.
+const DURATION: Duration = Duration::from_secs(3);
+
+/// Number of client threads sending queries.
+const CLIENTS: usize = 16;
+
+/// Number of queries each client keeps in flight.
+const WINDOW: usize = 8;
+
+/// Address that the server listens on.
+const ADDR: &str = "127.0.0.1:5399";
+
+/// Server process that is killed when dropped.
+struct Server(Child);
+
+impl Server {
+    /// Starts the server with the given number of workers and waits until it
+    /// answers queries.
+    fn start(zone: &str, workers: usize, query: &[u8]) -> Self {
+        let child = Command::new(env!("CARGO_BIN_EXE_dex-server"))
+            .args(["--zone", zone, "--listen", ADDR, "--log-level", "off"])
+            .args(["--workers", &workers.to_string()])
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("failed to start dex-server");
+        let server = Self(child);
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        for _ in 0..50 {
+            socket.send_to(query, ADDR).unwrap();
+            if socket.recv(&mut [0; 512]).is_ok() {
+                return server;
+            }
+        }
+        panic!("dex-server did not start");
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Sends queries from several clients until the time is up and returns the
+/// number of responses received per second.
+fn measure(query: &[u8]) -> f64 {
+    let addr = SocketAddr::from_str(ADDR).unwrap();
+    let done = Arc::new(AtomicBool::new(false));
+    let responses = Arc::new(AtomicU64::new(0));
+
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|_| {
+            let query = query.to_vec();
+            let done = done.clone();
+            let responses = responses.clone();
+            thread::spawn(move || {
+                let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+                socket.connect(addr).unwrap();
+                socket
+                    .set_read_timeout(Some(Duration::from_millis(50)))
+                    .unwrap();
+                let mut buf = [0; 512];
+                for _ in 0..WINDOW {
+                    socket.send(&query).unwrap();
+                }
+                while !done.load(Ordering::Relaxed) {
+                    // a lost query is replaced after a timeout
+                    if socket.recv(&mut buf).is_ok() {
+                        responses.fetch_add(1, Ordering::Relaxed);
+                    }
+                    socket.send(&query).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    let start = Instant::now();
+    thread::sleep(DURATION);
+    done.store(true, Ordering::Relaxed);
+    let count = responses.load(Ordering::Relaxed);
+    let elapsed = start.elapsed();
+    for client in clients {
+        client.join().unwrap();
+    }
+
+    count as f64 / elapsed.as_secs_f64()
+}
+
+fn main() {
+    let zone = std::env::temp_dir().join("dex-load.zone");
+    fs::write(
+        &zone,
+        "$ORIGIN example.com.\n\
+         @ 60 IN SOA ns admin 1 7200 3600 1209600 300\n\
+         $GENERATE 1-10000 host-$ 60 IN A 192.0.2.1\n",
+    )
+    .unwrap();
+
+    let mut request = Message::new();
+    request.header.question_count = 1;
+    request.questions = vec![Question {
+        name: Name::from_str("host-5000.example.com.").unwrap(),
+        q_type: QuestionType::A,
+        q_class: QuestionClass::In,
+    }];
+    let mut query = Bytes::new();
+    request.to_bytes(&mut query);
+
+    let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+    println!("{cpus} CPUs, {CLIENTS} clients with {WINDOW} queries in flight each");
+    println!("{:>8} {:>12} {:>8}", "workers", "queries/s", "speedup");
+
+    let mut baseline = None;
+    let mut workers = 1;
+    loop {
+        let _server = Server::start(zone.to_str().unwrap(), workers, query.used());
+        let rate = measure(query.used());
+        let baseline = *baseline.get_or_insert(rate);
+        println!("{workers:>8} {rate:>12.0} {:>7.2}x", rate / baseline);
+
+        if workers >= cpus {
+            break;
+        }
+        workers = (workers * 2).min(cpus);
+    }
+
+    let _ = fs::remove_file(zone);
+}
@@ -0,0 +1,68 @@
+//! Benchmarks for looking up names in a large zone.
+//!
+//! Run with `cargo bench`.
+
+use std::str::FromStr;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use dex::{Name, QuestionType, Zone};
+
+/// Number of hosts in the benchmark zone.
+const HOSTS: usize = 200_000;
+
+/// Builds a zone with an address record for each host, a wildcard and a
+/// delegation.
+fn large_zone() -> Zone {
+    let input = format!(
+        "$ORIGIN example.com.\n\
+         @ 60 IN SOA ns admin 1 7200 3600 1209600 300\n\
+         $GENERATE 1-{HOSTS} host-$ 60 IN A 192.0.2.1\n\
+         *.wild 60 IN A 192.0.2.2\n\
+         sub 60 IN NS ns.sub\n"
+    );
+    Zone::from_master(&input, None).unwrap()
+}
+
+fn lookups(c: &mut Criterion) {
+    let zone = large_zone();
+    let name = |s: &str| Name::from_str(s).unwrap();
+
+    let host = name(&format!("host-{}.example.com.", HOSTS / 2));
+    c.bench_function("find_with_name", |b| {
+        b.iter(|| zone.find_with_name(black_box(&host)))
+    });
+
+    let missing = name("missing.example.com.");
+    c.bench_function("contains_name", |b| {
+        b.iter(|| zone.contains_name(black_box(&missing)))
+    });
+
+    let below = name("a.b.c.sub.example.com.");
+    c.bench_function("closest_encloser", |b| {
+        b.iter(|| zone.closest_encloser(black_box(&below)))
+    });
+
+    let wild = name("anything.wild.example.com.");
+    c.bench_function("find_wildcard", |b| {
+        b.iter(|| zone.find_wildcard(black_box(&wild)))
+    });
+
+    c.bench_function("successor", |b| {
+        b.iter(|| zone.successor(black_box(&missing)))
+    });
+
+    // the path that the server takes to answer a query
+    for (label, name) in [
+        ("answer", &host),
+        ("name_error", &missing),
+        ("wildcard", &wild),
+        ("referral", &below),
+    ] {
+        c.bench_function(&format!("lookup_{label}"), |b| {
+            b.iter(|| zone.lookup(black_box(name), black_box(&QuestionType::A)))
+        });
+    }
+}
+
+criterion_group!(benches, lookups);
+criterion_main!(benches);
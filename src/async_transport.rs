@@ -0,0 +1,245 @@
+use std::{future::Future, net::SocketAddr, time::Duration};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{lookup_host, TcpStream, UdpSocket},
+    time::{timeout_at, Instant},
+};
+
+use crate::{
+    udp::{bind_random_port, TIMEOUT},
+    Bytes, FallbackTransport, Message, MockTransport, Transport, TransportError,
+};
+
+/// A way of sending DNS requests to a nameserver without blocking the
+/// calling thread.
+///
+/// This is the async counterpart of [`Transport`], for use on a tokio
+/// runtime.
+pub trait AsyncTransport {
+    /// Sends a DNS request and returns the response.
+    fn send(
+        &self,
+        request: Message,
+    ) -> impl Future<Output = Result<Message, TransportError>> + Send;
+}
+
+/// Async message transport over UDP.
+///
+/// Requests are sent as by [`UdpTransport`](crate::UdpTransport).
+pub struct AsyncUdpTransport {
+    nameserver: String,
+    max_response_size: u16,
+    timeout: Duration,
+}
+
+impl AsyncUdpTransport {
+    /// Creates a new AsyncUdpTransport object.
+    pub fn new(nameserver: String, max_size: u16) -> Self {
+        Self {
+            nameserver,
+            max_response_size: max_size,
+            timeout: TIMEOUT,
+        }
+    }
+
+    /// Sets the time to wait for a response (5 seconds by default).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl AsyncTransport for AsyncUdpTransport {
+    async fn send(&self, mut request: Message) -> Result<Message, TransportError> {
+        let addr = resolve(&self.nameserver).await?;
+        let socket = bind_random_port(addr)?;
+        socket.set_nonblocking(true)?;
+        let socket = UdpSocket::from_std(socket)?;
+        socket.connect(addr).await?;
+
+        request.header.id = rand::random();
+        let mut request_bytes = Bytes::new();
+        request.to_bytes(&mut request_bytes);
+        socket.send(request_bytes.used()).await?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut response_buf = vec![0; self.max_response_size as usize];
+        loop {
+            let len = match timeout_at(deadline, socket.recv(&mut response_buf)).await {
+                Ok(received) => received?,
+                Err(_) => return Err(TransportError::Timeout { addr }),
+            };
+
+            let mut response_bytes = Bytes::from_buf(&response_buf[..len]);
+            match Message::from_bytes(&mut response_bytes) {
+                Ok(response) if response.is_response_to(&request) => return Ok(response),
+                _ => continue,
+            }
+        }
+    }
+}
+
+/// Async message transport over TCP.
+///
+/// Requests are sent as by [`TcpTransport`](crate::TcpTransport).
+pub struct AsyncTcpTransport {
+    nameserver: String,
+    timeout: Duration,
+}
+
+impl AsyncTcpTransport {
+    /// Creates a new AsyncTcpTransport object.
+    pub fn new(nameserver: String) -> Self {
+        Self {
+            nameserver,
+            timeout: TIMEOUT,
+        }
+    }
+
+    /// Sets the time to wait for the whole exchange with the nameserver (5
+    /// seconds by default).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sends a request to the nameserver at the given address and reads the
+    /// response.
+    async fn exchange(
+        &self,
+        addr: SocketAddr,
+        mut request: Message,
+    ) -> Result<Message, TransportError> {
+        let mut socket = TcpStream::connect(addr).await?;
+
+        request.header.id = rand::random();
+        let mut request_bytes = Bytes::new();
+        request.to_bytes(&mut request_bytes);
+        let mut message = (request_bytes.used().len() as u16).to_be_bytes().to_vec();
+        message.extend_from_slice(request_bytes.used());
+        socket.write_all(&message).await?;
+
+        let response_len = socket.read_u16().await?;
+        let mut response_buf = vec![0; response_len as usize];
+        socket.read_exact(&mut response_buf).await?;
+        let mut response_bytes = Bytes::from_buf(&response_buf);
+        let response = Message::from_bytes(&mut response_bytes)?;
+
+        if !response.is_response_to(&request) {
+            return Err(TransportError::Mismatch { addr });
+        }
+
+        Ok(response)
+    }
+}
+
+impl AsyncTransport for AsyncTcpTransport {
+    async fn send(&self, request: Message) -> Result<Message, TransportError> {
+        let addr = resolve(&self.nameserver).await?;
+        let deadline = Instant::now() + self.timeout;
+        match timeout_at(deadline, self.exchange(addr, request)).await {
+            Ok(response) => response,
+            Err(_) => Err(TransportError::Timeout { addr }),
+        }
+    }
+}
+
+impl<P, F> AsyncTransport for FallbackTransport<P, F>
+where
+    P: AsyncTransport + Sync,
+    F: AsyncTransport + Sync,
+{
+    async fn send(&self, request: Message) -> Result<Message, TransportError> {
+        match self.primary.send(request.clone()).await? {
+            response if response.header.is_truncated => self.fallback.send(request).await,
+            response => Ok(response),
+        }
+    }
+}
+
+impl AsyncTransport for MockTransport {
+    /// Returns the next scripted response, as [`Transport::send`] does.
+    async fn send(&self, request: Message) -> Result<Message, TransportError> {
+        Transport::send(self, request)
+    }
+}
+
+/// Resolves the address of a nameserver without blocking, as
+/// [`resolve`](crate::udp::resolve) does.
+async fn resolve(nameserver: &str) -> Result<SocketAddr, TransportError> {
+    if let Ok(ip) = nameserver.parse() {
+        return Ok(SocketAddr::new(ip, 53));
+    }
+
+    let addrs = if nameserver.contains(':') {
+        lookup_host(nameserver)
+            .await
+            .map(|addrs| addrs.collect::<Vec<_>>())
+    } else {
+        lookup_host((nameserver, 53))
+            .await
+            .map(|addrs| addrs.collect())
+    };
+
+    addrs
+        .map_err(|e| TransportError::Resolve {
+            nameserver: nameserver.to_owned(),
+            source: Some(e),
+        })?
+        .into_iter()
+        .next()
+        .ok_or_else(|| TransportError::Resolve {
+            nameserver: nameserver.to_owned(),
+            source: None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::net::UdpSocket;
+
+    use crate::{
+        AsyncTransport, AsyncUdpTransport, Bytes, FallbackTransport, Message, MockTransport,
+        TransportError,
+    };
+
+    #[tokio::test]
+    async fn udp_exchange_and_timeout() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let nameserver = server.local_addr().unwrap().to_string();
+        let transport =
+            AsyncUdpTransport::new(nameserver, 512).with_timeout(Duration::from_millis(100));
+
+        let (response, _) = tokio::join!(transport.send(Message::new()), async {
+            let mut buf = [0; 512];
+            let (len, client) = server.recv_from(&mut buf).await.unwrap();
+            let mut response = Message::from_bytes(&mut Bytes::from_buf(&buf[..len])).unwrap();
+            response.header.is_response = true;
+            let mut bytes = Bytes::new();
+            response.to_bytes(&mut bytes);
+            server.send_to(bytes.used(), client).await.unwrap();
+        });
+        assert!(response.unwrap().header.is_response);
+
+        let err = transport.send(Message::new()).await.unwrap_err();
+        assert!(matches!(err, TransportError::Timeout { .. }));
+    }
+
+    #[tokio::test]
+    async fn fallback_on_truncation() {
+        let udp = MockTransport::new();
+        let tcp = MockTransport::new();
+        let mut truncated = Message::new();
+        truncated.header.is_truncated = true;
+        udp.push_response(truncated);
+        tcp.push_response(Message::new());
+
+        let transport = FallbackTransport::new(udp, tcp.clone());
+        let response = AsyncTransport::send(&transport, Message::new()).await;
+        assert!(!response.unwrap().header.is_truncated);
+        assert_eq!(tcp.requests().len(), 1);
+    }
+}
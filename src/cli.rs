@@ -1,17 +1,18 @@
 mod logger;
 mod minimal;
 
-use std::{fs, process::ExitCode, str::FromStr};
+use std::{fmt::Display, fs, process::ExitCode, str::FromStr, time::Duration};
 
 use anyhow::{bail, Context};
 use clap::{ArgAction, Parser, ValueEnum};
 use dex::{
-    Message, Name, Question, QuestionClass, QuestionType, Record, ResponseCode, TcpTransport,
-    UdpTransport,
+    Message, Name, Question, QuestionClass, QuestionType, Record, ResolvConf, Resolver,
+    ResponseCode, TcpTransport, UdpTransport,
 };
 use log::{error, warn};
 use logger::init_logger;
 use minimal::MinimalRecord;
+use serde::Serialize;
 
 #[derive(Parser, Debug)]
 #[command(version, about, max_term_width = 80)]
@@ -27,7 +28,8 @@ struct Cli {
     /// The following arguments are supported:
     ///
     /// [type]: The type of record to search for, specified using the alphabetic
-    /// code for the record (e.g., A, MX, NS, ...). (default: A)
+    /// code for the record (e.g., A, MX, NS, ...) or the generic form
+    /// TYPE<code> (e.g., TYPE65). (default: A)
     ///
     /// [class]: The class of the request, specified using the alphabetic code
     /// for the class (e.g., IN, HS, ...). (default: IN)
@@ -35,7 +37,7 @@ struct Cli {
     /// [nameserver]: The nameserver to send the request to, specified with an @
     /// symbol in front of the name (e.g., @8.8.8.8). The nameserver may include
     /// a port number (e.g., @8.8.8.8:53), and the host may be specified using a
-    /// hostname or an IP address. (default: system default nameserver)
+    /// hostname or an IP address. (default: system default nameservers)
     ///
     /// Each type of argument may be specified only once and may be specified in
     /// any order.
@@ -60,6 +62,22 @@ struct Cli {
     /// Print output in JSON format. (default: unstructured text)
     #[arg(long)]
     json: bool,
+    /// Show internationalized names in Unicode instead of as A-labels
+    /// (xn--...). (default: A-labels)
+    #[arg(long)]
+    unicode: bool,
+    /// Seconds to wait for a response to the first attempt at each
+    /// nameserver. The time is doubled for each further attempt. (default:
+    /// system default, or 5)
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: Option<u64>,
+    /// Number of times to try each nameserver. (default: system default, or 2)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    attempts: Option<u64>,
+    /// Start with a random nameserver instead of the first. (default: system
+    /// default)
+    #[arg(long)]
+    rotate: bool,
 }
 
 /// The amount of information to include in the output.
@@ -129,7 +147,7 @@ impl TryFrom<Vec<String>> for Args {
 }
 
 fn main() -> ExitCode {
-    init_logger();
+    init_logger(log::LevelFilter::Warn);
 
     let Cli {
         domain,
@@ -140,6 +158,10 @@ fn main() -> ExitCode {
         minimal,
         full,
         json,
+        unicode,
+        timeout,
+        attempts,
+        rotate,
     } = Cli::parse();
 
     let Args {
@@ -181,6 +203,7 @@ fn main() -> ExitCode {
     let max_response_size = if edns { 4096 } else { 512 };
 
     if edns {
+        request.header.additional_count = 1;
         request.additional_records = vec![Record::Opt {
             name: Name::from_str(".").unwrap(),
             max_response_size,
@@ -191,28 +214,57 @@ fn main() -> ExitCode {
         }];
     }
 
-    let nameserver = nameserver.unwrap_or(find_default_nameserver());
-
-    let response = {
-        if tcp {
-            TcpTransport::new(nameserver).send(request)
-        } else if udp {
-            UdpTransport::new(nameserver, max_response_size).send(request)
-        } else {
-            let response =
-                UdpTransport::new(nameserver.clone(), max_response_size).send(request.clone());
-            if response.header.is_truncated {
-                TcpTransport::new(nameserver).send(request)
-            } else {
-                response
-            }
+    let mut conf = match load_resolv_conf() {
+        Ok(conf) => conf,
+        // the system configuration is not needed if a nameserver is given
+        Err(_) if nameserver.is_some() => ResolvConf::default(),
+        Err(e) => {
+            error!("{e:?}");
+            return ExitCode::from(1);
+        }
+    };
+    if let Some(nameserver) = nameserver {
+        conf.nameservers = vec![nameserver];
+    }
+    if let Some(timeout) = timeout {
+        conf.timeout = Duration::from_secs(timeout);
+    }
+    if let Some(attempts) = attempts {
+        conf.attempts = attempts as usize;
+    }
+    conf.rotate |= rotate;
+
+    conf.edns0 = edns;
+
+    let resolver = Resolver::new(conf);
+    let resolver = if tcp {
+        resolver.with_transport(|nameserver, timeout| {
+            Box::new(TcpTransport::new(nameserver.to_owned()).with_timeout(timeout))
+        })
+    } else if udp {
+        resolver.with_transport(move |nameserver, timeout| {
+            Box::new(
+                UdpTransport::new(nameserver.to_owned(), max_response_size).with_timeout(timeout),
+            )
+        })
+    } else {
+        resolver
+    };
+
+    let response = resolver.send(request);
+
+    let response = match response.context("failed to send request") {
+        Ok(response) => response,
+        Err(e) => {
+            error!("{e:?}");
+            return ExitCode::from(1);
         }
     };
 
     match (detail, format, &response.header.resp_code) {
         (Detail::Minimal, Format::Text, ResponseCode::Success) => {
             for record in response.answer_records {
-                println!("{}", MinimalRecord::from(record))
+                println!("{}", show(&MinimalRecord::from(record), unicode))
             }
         }
 
@@ -220,31 +272,28 @@ fn main() -> ExitCode {
             let min_answer_records = response
                 .answer_records
                 .into_iter()
-                .map(|record| MinimalRecord::from(record))
+                .map(MinimalRecord::from)
                 .collect::<Vec<_>>();
 
-            println!("{}", serde_json::to_string(&min_answer_records).unwrap());
+            println!("{}", to_json(&min_answer_records, unicode));
         }
 
-        (Detail::Minimal, _, status @ _) => {
+        (Detail::Minimal, _, status) => {
             eprintln!("status: {status}");
             return ExitCode::from(1);
         }
 
         (Detail::Standard, Format::Text, ResponseCode::Success) => {
             for record in &response.answer_records {
-                println!("{record}")
+                println!("{}", show(record, unicode))
             }
         }
 
         (Detail::Standard, Format::Json, ResponseCode::Success) => {
-            println!(
-                "{}",
-                serde_json::to_string(&response.answer_records).unwrap()
-            );
+            println!("{}", to_json(&response.answer_records, unicode));
         }
 
-        (Detail::Standard, _, status @ _) => {
+        (Detail::Standard, _, status) => {
             eprintln!("status: {status}");
             return ExitCode::from(1);
         }
@@ -253,30 +302,83 @@ fn main() -> ExitCode {
             println!("{}", response.header);
 
             for question in &response.questions {
-                println!("{question} ?");
+                println!("{} ?", show(question, unicode));
             }
 
             for record in &response.answer_records {
-                println!("{record}")
+                println!("{}", show(record, unicode))
             }
 
             for record in &response.authority_records {
-                println!("{record} !")
+                println!("{} !", show(record, unicode))
             }
 
             for record in &response.additional_records {
-                println!("{record} +")
+                println!("{} +", show(record, unicode))
             }
         }
 
         (Detail::Full, Format::Json, _) => {
-            println!("{}", serde_json::to_string(&response).unwrap());
+            println!("{}", to_json(&response, unicode));
         }
     }
 
     ExitCode::default()
 }
 
+/// Formats a value for text output, with names in Unicode if requested.
+fn show(value: &impl Display, unicode: bool) -> String {
+    if unicode {
+        format!("{value:#}")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Formats a value as JSON, with names in Unicode if requested.
+fn to_json(value: &impl Serialize, unicode: bool) -> String {
+    if !unicode {
+        return serde_json::to_string(value).unwrap();
+    }
+
+    let mut json = serde_json::to_value(value).unwrap();
+    names_to_unicode(&mut json);
+    json.to_string()
+}
+
+/// Converts the A-labels of names in JSON output to Unicode.
+///
+/// Names are the values of the fields that hold names in questions and
+/// records.
+fn names_to_unicode(json: &mut serde_json::Value) {
+    const NAME_FIELDS: [&str; 7] = [
+        "name",
+        "host",
+        "target",
+        "origin",
+        "mailbox",
+        "r_mailbox",
+        "e_mailbox",
+    ];
+
+    match json {
+        serde_json::Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                match value {
+                    serde_json::Value::String(text) if NAME_FIELDS.contains(&key.as_str()) => {
+                        if let Ok(name) = Name::from_str(text) {
+                            *text = format!("{name:#}");
+                        }
+                    }
+                    _ => names_to_unicode(value),
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(names_to_unicode),
+        _ => {}
+    }
+}
+
 /// Represents the hosts file found on most operating systems.
 struct Hosts;
 
@@ -300,8 +402,7 @@ impl Hosts {
             if line.starts_with("#") || line.trim().is_empty() {
                 continue;
             }
-            let mut parts = line.split_whitespace();
-            while let Some(in_host) = parts.next() {
+            for in_host in line.split_whitespace() {
                 if in_host == host || (in_host.to_owned() + ".") == host {
                     return true;
                 }
@@ -311,25 +412,29 @@ impl Hosts {
     }
 }
 
-/// Finds the default nameserver for this operating system.
+/// Loads the resolver configuration for this operating system.
 #[cfg(unix)]
-fn find_default_nameserver() -> String {
-    let config = fs::read_to_string("/etc/resolv.conf").unwrap();
-    for line in config.lines() {
-        let mut parts = line.split_whitespace();
-        if matches!(parts.next(), Some("nameserver")) {
-            match parts.next() {
-                Some(addr) => return addr.to_owned(),
-                None => panic!("resolver config is malformed"),
-            }
-        }
+fn load_resolv_conf() -> anyhow::Result<ResolvConf> {
+    let conf = ResolvConf::from_file("/etc/resolv.conf")?;
+    if conf.nameservers.is_empty() {
+        bail!("no nameservers found in /etc/resolv.conf");
     }
-    panic!("failed to locate default nameserver")
+    Ok(conf)
+}
+
+/// Loads the resolver configuration for this operating system.
+#[cfg(windows)]
+fn load_resolv_conf() -> anyhow::Result<ResolvConf> {
+    let nameserver = find_default_nameserver().context("failed to locate default nameserver")?;
+    Ok(ResolvConf {
+        nameservers: vec![nameserver],
+        ..ResolvConf::default()
+    })
 }
 
 /// Finds the default nameserver for this operating system.
 #[cfg(windows)]
-fn find_default_nameserver() -> String {
+fn find_default_nameserver() -> Option<String> {
     use std::{
         io,
         net::{IpAddr, UdpSocket},
@@ -346,27 +451,26 @@ fn find_default_nameserver() -> String {
 
     let ip = get_ipv4().ok();
 
-    let adapters = ipconfig::get_adapters().unwrap();
+    let adapters = ipconfig::get_adapters().ok()?;
     let active_adapters = adapters.iter().filter(|a| {
         a.oper_status() == ipconfig::OperStatus::IfOperStatusUp && !a.gateways().is_empty()
     });
 
-    if let Some(dns_server) = active_adapters
+    active_adapters
         .clone()
         .find(|a| ip.map(|ip| a.ip_addresses().contains(&ip)).unwrap_or(false))
         .map(|a| a.dns_servers().first())
         .flatten()
-    {
-        let nameserver = dns_server.to_string();
-        return nameserver;
-    }
-
-    panic!("failed to locate default nameserver")
+        .map(|dns_server| dns_server.to_string())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Hosts;
+    use std::str::FromStr;
+
+    use dex::{Name, Record};
+
+    use crate::{to_json, Hosts, MinimalRecord};
 
     #[test]
     fn hosts_contains() {
@@ -374,4 +478,49 @@ mod tests {
         let contains = Hosts::contains_inner(input, "localhost");
         assert!(contains);
     }
+
+    #[test]
+    fn json_names_to_unicode() {
+        let record = Record::Cname {
+            name: Name::from_str("xn--bcher-kva.example.").unwrap(),
+            class: dex::Class::In,
+            ttl: 60,
+            host: Name::from_str("xn--ls8h.la.").unwrap(),
+        };
+        assert_eq!(
+            to_json(&[record], true),
+            r#"[{"type":"CNAME","name":"bücher.example.","class":"IN","ttl":60,"host":"💩.la."}]"#
+        );
+    }
+
+    #[test]
+    fn minimal_records_in_presentation_format() {
+        let name = Name::from_str("example.com.").unwrap();
+        let caa = Record::Caa {
+            name: name.clone(),
+            class: dex::Class::In,
+            ttl: 60,
+            flags: 0,
+            tag: "iodef".to_owned(),
+            value: "mailto:\"ü\"@example.com".to_owned(),
+        };
+        assert_eq!(
+            MinimalRecord::from(caa).to_string(),
+            r#"0 iodef "mailto:\"\195\188\"@example.com""#
+        );
+
+        let unknown = Record::Unknown {
+            name,
+            class: dex::Class::In,
+            ttl: 60,
+            r_type: 1234,
+            data: vec![0xde, 0xad],
+        };
+        let minimal = MinimalRecord::from(unknown);
+        assert_eq!(minimal.to_string(), r"TYPE1234 \# 2 dead");
+        assert_eq!(
+            serde_json::to_string(&minimal).unwrap(),
+            r#"{"r_type":1234,"data":"\\# 2 dead"}"#
+        );
+    }
 }
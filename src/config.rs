@@ -0,0 +1,242 @@
+use std::{
+    collections::HashSet,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use dex::Name;
+use serde::Deserialize;
+
+/// Configuration for the DNS server.
+///
+/// The configuration is loaded from a TOML file, and any options given on
+/// the command line take precedence over those in the file.
+///
+/// # Example
+///
+/// ```toml
+/// listen = ["0.0.0.0:53", "[::]:53"]
+/// log_level = "info"
+/// workers = 4
+/// tcp_idle_timeout = 10
+/// tcp_max_connections = 100
+///
+/// [[zones]]
+/// path = "/etc/dex/example.com.zone"
+/// origin = "example.com."
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Addresses (including ports) to listen on.
+    #[serde(default = "default_listen")]
+    pub listen: Vec<SocketAddr>,
+    /// Zones to serve.
+    #[serde(default)]
+    pub zones: Vec<ZoneConfig>,
+    /// Maximum level of log messages to print.
+    #[serde(default)]
+    pub log_level: LogLevel,
+    /// Number of threads that serve queries on each UDP address, each with a
+    /// socket of its own where SO_REUSEPORT is available.
+    #[serde(default = "default_workers")]
+    pub workers: usize,
+    /// Seconds after which an idle TCP connection is closed.
+    #[serde(default = "default_tcp_idle_timeout")]
+    pub tcp_idle_timeout: u64,
+    /// Maximum number of concurrent TCP connections.
+    #[serde(default = "default_tcp_max_connections")]
+    pub tcp_max_connections: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen: default_listen(),
+            zones: vec![],
+            log_level: LogLevel::default(),
+            workers: default_workers(),
+            tcp_idle_timeout: default_tcp_idle_timeout(),
+            tcp_max_connections: default_tcp_max_connections(),
+        }
+    }
+}
+
+/// Returns the default addresses to listen on.
+fn default_listen() -> Vec<SocketAddr> {
+    vec![SocketAddr::from(([0, 0, 0, 0], 5380))]
+}
+
+/// Returns the default number of UDP workers per address.
+fn default_workers() -> usize {
+    1
+}
+
+/// Returns the default idle timeout for TCP connections, in seconds.
+fn default_tcp_idle_timeout() -> u64 {
+    10
+}
+
+/// Returns the default maximum number of concurrent TCP connections.
+fn default_tcp_max_connections() -> usize {
+    100
+}
+
+impl Config {
+    /// Loads a Config from a TOML file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let input = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        toml::from_str(&input)
+            .with_context(|| format!("failed to parse config file {}", path.display()))
+    }
+
+    /// Checks that the configuration can be used to start a server.
+    pub fn validate(&self) -> Result<()> {
+        if self.listen.is_empty() {
+            bail!("no listen addresses configured");
+        }
+
+        let mut addrs = HashSet::new();
+        for addr in &self.listen {
+            if !addrs.insert(addr) {
+                bail!("listen address {addr} is configured more than once");
+            }
+        }
+
+        if self.workers == 0 {
+            bail!("workers must be at least 1");
+        }
+
+        if self.tcp_idle_timeout == 0 {
+            bail!("tcp_idle_timeout must be at least 1");
+        }
+
+        if self.tcp_max_connections == 0 {
+            bail!("tcp_max_connections must be at least 1");
+        }
+
+        if self.zones.is_empty() {
+            bail!("no zones configured");
+        }
+
+        Ok(())
+    }
+}
+
+/// Configuration for a zone.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ZoneConfig {
+    /// Path to the zone file, in TOML or master file format.
+    pub path: PathBuf,
+    /// Origin that relative names in a master file are resolved against
+    /// until the file sets one with `$ORIGIN`.
+    #[serde(default)]
+    pub origin: Option<Name>,
+}
+
+impl FromStr for ZoneConfig {
+    type Err = anyhow::Error;
+
+    /// Parses a zone given on the command line, as a path optionally
+    /// followed by `=` and the origin of the zone.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.rsplit_once('=') {
+            Some((path, origin)) => Ok(Self {
+                path: PathBuf::from(path),
+                origin: Some(
+                    Name::from_str(origin).with_context(|| format!("invalid origin {origin}"))?,
+                ),
+            }),
+            None => Ok(Self {
+                path: PathBuf::from(s),
+                origin: None,
+            }),
+        }
+    }
+}
+
+/// The maximum level of log messages to print.
+#[derive(Debug, Default, Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// Print no messages.
+    Off,
+    /// Print errors.
+    Error,
+    /// Print errors and warnings.
+    Warn,
+    /// Print errors, warnings and information about the server.
+    #[default]
+    Info,
+    /// Print all of the above and information about each query.
+    Debug,
+    /// Print all messages.
+    Trace,
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => log::LevelFilter::Off,
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Config, ZoneConfig};
+
+    #[test]
+    fn parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            listen = ["127.0.0.1:53", "[::1]:5353"]
+            log_level = "debug"
+
+            [[zones]]
+            path = "example.com.zone"
+
+            [[zones]]
+            path = "example.net.zone"
+            origin = "example.net."
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.listen[1].port(), 5353);
+        assert!(config.zones[0].origin.is_none());
+        assert_eq!(
+            config.zones[1].origin.as_ref().unwrap().to_string(),
+            "example.net."
+        );
+        assert_eq!(config.workers, 1);
+        assert!(config.validate().is_ok());
+
+        let config: Config = toml::from_str("workers = 0").unwrap();
+        assert!(config.validate().is_err());
+
+        assert!(toml::from_str::<Config>("port = 53").is_err());
+    }
+
+    #[test]
+    fn parse_zone_argument() {
+        let zone = ZoneConfig::from_str("db.example").unwrap();
+        assert!(zone.origin.is_none());
+
+        let zone = ZoneConfig::from_str("db.example=example.com.").unwrap();
+        assert_eq!(zone.path.to_str(), Some("db.example"));
+        assert_eq!(zone.origin.unwrap().to_string(), "example.com.");
+    }
+}
@@ -1,110 +1,288 @@
+#[cfg(feature = "async")]
+mod async_transport;
+mod master;
+mod resolv;
+mod resolver;
+mod svcb;
 mod tcp;
+mod transport;
 mod udp;
+#[cfg(feature = "async")]
+pub use async_transport::{AsyncTcpTransport, AsyncTransport, AsyncUdpTransport};
+pub use resolv::ResolvConf;
+pub use resolver::{Answer, ResolveError, Resolver};
+pub use svcb::SvcParam;
 pub use tcp::TcpTransport;
+pub use transport::{FallbackTransport, MockTransport, Transport, TransportError};
 pub use udp::UdpTransport;
 
 use std::{
-    collections::HashMap,
-    convert::Infallible,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
     fmt::Display,
+    hash::{Hash, Hasher},
     net::{Ipv4Addr, Ipv6Addr},
+    ops::Bound,
+    path::Path,
     str::FromStr,
 };
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::{de::Visitor, Deserialize, Serialize};
 
 /// A DNS label.
 ///
-/// A label must be shorter than 63 bytes.
-#[derive(Debug, PartialEq, Eq, Clone)]
-struct Label(String);
+/// A label is a string of up to 63 arbitrary bytes. Labels are compared and
+/// hashed case-insensitively (RFC 4343), but keep their original case.
+#[derive(Debug, Clone)]
+struct Label(Vec<u8>);
 
 impl Label {
-    /// Creates a new Label from a string.
-    fn from_str(text: &str) -> Self {
-        assert!(text.len() < 63);
-
-        lazy_static! {
-            static ref RE: Regex =
-                Regex::new("^*|[[:alpha:]]([[:alpha:]0-9-]*[[:alpha:]0-9])?$").unwrap();
+    /// Creates a new Label from bytes.
+    fn new(bytes: Vec<u8>) -> Result<Self, ParseNameError> {
+        if bytes.len() > 63 {
+            return Err(ParseNameError::LabelTooLong);
         }
+        Ok(Self(bytes))
+    }
 
-        assert!(text.is_empty() || RE.is_match(text));
-
-        Self(text.to_owned())
+    /// Creates a new Label from its presentation format, in which bytes may
+    /// be escaped as `\DDD` or `\X` (RFC 1035 section 5.1).
+    fn from_text(text: &str) -> Result<Self, ParseNameError> {
+        Self::new(unescape(text).ok_or(ParseNameError::BadEscape)?)
     }
 
     /// Creates a new Label from a byte stream.
-    fn from_bytes(bytes: &mut Bytes) -> Self {
-        let len = bytes.read().unwrap();
-        let bytez = bytes.read_exact(len as usize).unwrap();
-        let text = String::from_utf8(bytez).unwrap();
-        Self::from_str(&text)
+    fn from_bytes(bytes: &mut Bytes) -> Result<Self, DecodeError> {
+        let offset = bytes.pos();
+        let len = bytes.read()?;
+        if len > 63 {
+            return Err(DecodeError::BadLabel { offset });
+        }
+        Ok(Self(bytes.read_exact(len as usize)?))
     }
 
     /// Converts a Label to a byte stream.
     fn to_bytes(&self, bytes: &mut Bytes) {
         bytes.write(self.0.len() as u8);
-        bytes.write_all(self.0.as_bytes());
+        bytes.write_all(&self.0);
     }
 
     /// Returns the length of the label.
     fn len(&self) -> u8 {
         self.0.len() as u8
     }
+
+    /// Returns true if this is the root label.
+    fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns true if this is a wildcard label (`*`).
+    fn is_wildcard(&self) -> bool {
+        self.0 == b"*"
+    }
+
+    /// Returns true if the label is valid in a host name, that is, if it
+    /// consists of letters, digits and hyphens and does not start or end with
+    /// a hyphen (RFC 952 and RFC 1123 section 2.1).
+    fn is_hostname(&self) -> bool {
+        lazy_static! {
+            static ref RE: Regex = Regex::new("^[[:alnum:]]([[:alnum:]-]*[[:alnum:]])?$").unwrap();
+        }
+
+        std::str::from_utf8(&self.0).is_ok_and(|text| RE.is_match(text))
+    }
+
+    /// Converts an A-label (`xn--...`) to the corresponding U-label.
+    ///
+    /// Returns None if the label is not a valid A-label.
+    fn to_unicode(&self) -> Option<String> {
+        let text = std::str::from_utf8(&self.0).ok()?;
+        if !text.get(..4)?.eq_ignore_ascii_case("xn--") {
+            return None;
+        }
+
+        let (unicode, result) = idna::domain_to_unicode(text);
+        result.ok().map(|_| unicode)
+    }
+
+    /// Returns the bytes of the label with ASCII letters in lowercase.
+    fn lowercase_bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.0.iter().map(|b| b.to_ascii_lowercase())
+    }
+}
+
+impl Display for Label {
+    /// Formats the label in presentation format, escaping special characters
+    /// with a backslash and other non-printable bytes as `\DDD`.
+    ///
+    /// In the alternate form (`{:#}`), valid A-labels are shown as U-labels.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            if let Some(text) = self.to_unicode() {
+                return write!(f, "{text}");
+            }
+        }
+
+        for byte in &self.0 {
+            match byte {
+                b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                    write!(f, "\\{}", *byte as char)?
+                }
+                b'!'..=b'~' => write!(f, "{}", *byte as char)?,
+                _ => write!(f, "\\{byte:03}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes the escape sequences in the presentation format of a label or
+/// character string (RFC 1035 section 5.1).
+///
+/// `\DDD` stands for the byte with the decimal value `DDD`, and `\X` for the
+/// character `X`. Returns None if an escape sequence is incomplete or out of
+/// range.
+pub(crate) fn unescape(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => chars.next()?,
+            c => {
+                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                continue;
+            }
+        };
+
+        if c.is_ascii_digit() {
+            let digits: String = std::iter::once(c).chain(chars.by_ref().take(2)).collect();
+            if digits.len() != 3 || !digits.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            bytes.push(digits.parse::<u8>().ok()?);
+        } else {
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+    }
+    Some(bytes)
+}
+
+/// Splits a text in presentation format on unescaped separators, e.g., a
+/// name on dots or a list of service parameter values on commas.
+pub(crate) fn split_unescaped(text: &str, separator: char) -> Vec<&str> {
+    let mut items = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == separator => {
+                items.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    items.push(&text[start..]);
+    items
+}
+
+impl PartialEq for Label {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_ignore_ascii_case(&other.0)
+    }
+}
+
+impl Eq for Label {}
+
+impl Hash for Label {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u8(self.len());
+        self.lowercase_bytes().for_each(|b| state.write_u8(b));
+    }
+}
+
+impl PartialOrd for Label {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Label {
+    /// Compares labels as lowercase byte strings, where a label that is a
+    /// prefix of another comes first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.lowercase_bytes().cmp(other.lowercase_bytes())
+    }
 }
 
 /// A fully qualified DNS domain name.
 ///
-/// A name must be shorter than 255 bytes. The last label in a name must be the
-/// root label ("") and all other labels must non-empty. When parsed from a
-/// relative name, the root label is inferred.
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// A name must be at most 255 bytes long. The last label in a name must be
+/// the root label ("") and all other labels must be non-empty. When parsed
+/// from a relative name, the root label is inferred.
+///
+/// Labels may contain arbitrary bytes, which are escaped in the presentation
+/// format as `\DDD` (or as `\X` for special characters such as `.`).
+/// Internationalized names are parsed from Unicode into A-labels (`xn--...`),
+/// and the alternate form of Display (`{:#}`) shows A-labels as Unicode.
+///
+/// Names are compared and hashed case-insensitively (RFC 4343), but keep
+/// their original case when they are displayed or encoded. Names are ordered
+/// in the canonical DNS name order (RFC 4034 section 6.1).
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Name {
     labels: Vec<Label>,
 }
 
 impl Name {
-    /// Creates a Name from labels.
-    fn from_labels(labels: Vec<Label>) -> Self {
-        assert!(!labels.is_empty());
-
-        let len = labels.len() + labels.iter().fold(0, |acc, l| acc + l.len() as usize);
-        assert!(len < 255);
-
+    /// Creates a Name from labels, which must end with the root label.
+    fn from_labels(labels: Vec<Label>) -> Result<Self, ParseNameError> {
         let Some((last, rest)) = labels.split_last() else {
-            unreachable!()
+            return Err(ParseNameError::EmptyLabel);
         };
+        if !last.is_root() || rest.iter().any(Label::is_root) {
+            return Err(ParseNameError::EmptyLabel);
+        }
 
-        assert_eq!(last.0, "");
-        for (i, label) in rest.iter().enumerate() {
-            assert_ne!(label.0, "");
-            if i != 0 {
-                assert_ne!(label.0, "*");
-            }
+        let len = labels.len() + labels.iter().map(|l| l.len() as usize).sum::<usize>();
+        if len > 255 {
+            return Err(ParseNameError::NameTooLong);
         }
 
-        Self { labels }
+        Ok(Self { labels })
+    }
+
+    /// Returns the root name.
+    pub fn root() -> Self {
+        Self {
+            labels: vec![Label(vec![])],
+        }
     }
 
     /// Creates a Name from a byte stream.
-    fn from_bytes(bytes: &mut Bytes) -> Self {
+    fn from_bytes(bytes: &mut Bytes) -> Result<Self, DecodeError> {
+        let start = bytes.pos();
         let mut labels = vec![];
+        let mut len = 0;
 
         let mut restore: Option<usize> = None;
         let mut max = bytes.pos();
         loop {
-            let signal = bytes.peek().unwrap();
+            let pos = bytes.pos();
+            let signal = bytes.peek()?;
             let is_ptr = (signal >> 6 & 3) == 3;
             if is_ptr {
-                let ptr = bytes.read_u16().unwrap();
+                let ptr = bytes.read_u16()?;
                 let offset = ptr & 0b0011_1111_1111_1111;
 
                 if offset as usize >= max {
-                    panic!("detected pointer loop")
+                    return Err(DecodeError::PointerLoop { offset: pos });
                 }
 
                 if restore.is_none() {
@@ -114,8 +292,12 @@ impl Name {
                 bytes.seek(offset as usize);
                 max = offset as usize;
             } else {
-                let label = Label::from_bytes(bytes);
+                let label = Label::from_bytes(bytes)?;
                 let is_root = label.len() == 0;
+                len += label.len() as usize + 1;
+                if len > 255 {
+                    return Err(DecodeError::NameTooLong { offset: start });
+                }
                 labels.push(label);
                 if is_root {
                     break;
@@ -127,7 +309,7 @@ impl Name {
             bytes.seek(restore);
         }
 
-        Self::from_labels(labels)
+        Ok(Self { labels })
     }
 
     /// Converts a Name to a byte stream.
@@ -155,16 +337,46 @@ impl Name {
         }
     }
 
+    /// Converts a Name to a byte stream without compression.
+    ///
+    /// Some record types (e.g., SRV) require that names in their record data
+    /// are never compressed.
+    fn to_bytes_uncompressed(&self, bytes: &mut Bytes) {
+        for label in &self.labels {
+            label.to_bytes(bytes);
+        }
+    }
+
     /// Returns true if this name represents the root name.
     pub fn is_root(&self) -> bool {
         self.labels.len() == 1
     }
 
+    /// Returns true if this name is a valid host name, that is, if all of its
+    /// labels consist of letters, digits and hyphens and do not start or end
+    /// with a hyphen (RFC 952 and RFC 1123 section 2.1).
+    ///
+    /// Names in general may contain arbitrary bytes (RFC 2181 section 11), so
+    /// this is only checked where a host name is required.
+    pub fn is_hostname(&self) -> bool {
+        !self.is_root()
+            && self
+                .labels
+                .iter()
+                .filter(|l| !l.is_root())
+                .all(Label::is_hostname)
+    }
+
+    /// Returns true if the first label of this name is a wildcard (`*`).
+    pub fn is_wildcard(&self) -> bool {
+        self.labels[0].is_wildcard()
+    }
+
     /// Returns an iterator over the suffixes of this name.
     ///
     /// Suffixes are returned in descending order based on length. The last
     /// element returned is the root name.
-    fn suffixes(&self) -> Suffixes {
+    fn suffixes(&self) -> Suffixes<'_> {
         Suffixes::new(self)
     }
 
@@ -172,35 +384,99 @@ impl Name {
     ///
     /// Ancestors are returned in ascending order based on length. The last
     /// element returned is the full name.
-    pub fn ancestors(&self) -> Ancestors {
+    pub fn ancestors(&self) -> Ancestors<'_> {
         Ancestors::new(self)
     }
 
+    /// Returns true if this name is equal to or below another name.
+    pub fn is_subdomain_of(&self, other: &Name) -> bool {
+        self.labels.ends_with(&other.labels)
+    }
+
+    /// Replaces a suffix of this name with another name.
+    ///
+    /// Returns None if this name is not below the suffix, or if the resulting
+    /// name would be longer than 255 bytes.
+    pub fn replace_suffix(&self, suffix: &Name, replacement: &Name) -> Option<Name> {
+        if !self.is_subdomain_of(suffix) {
+            return None;
+        }
+
+        let prefix = &self.labels[..self.labels.len() - suffix.labels.len()];
+        let labels = prefix.iter().chain(&replacement.labels).cloned().collect();
+        Self::from_labels(labels).ok()
+    }
+
+    /// Returns the wildcard child of this name.
+    ///
+    /// Returns None if the resulting name would be longer than 255 bytes.
+    pub fn to_child_wildcard(&self) -> Option<Name> {
+        let labels = std::iter::once(Label(b"*".to_vec()))
+            .chain(self.labels.iter().cloned())
+            .collect();
+        Self::from_labels(labels).ok()
+    }
+
     /// Returns a copy of the Name with the first label replaced with a
     /// wildcard.
+    ///
+    /// The wildcard of the root name is `*.`.
     pub fn to_wildcard(&self) -> Name {
-        let labels = std::iter::once("*".to_owned())
-            .chain(self.labels.iter().map(|l| l.0.clone()).skip(1))
-            .collect::<Vec<_>>()
-            .join(".");
-        Self::from_str(&labels).unwrap()
+        let rest = if self.is_root() {
+            &self.labels[..]
+        } else {
+            &self.labels[1..]
+        };
+        let labels = std::iter::once(Label(b"*".to_vec()))
+            .chain(rest.iter().cloned())
+            .collect();
+        Self { labels }
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Name {
+    /// Compares names label by label from the right, so a name comes directly
+    /// before its descendants (RFC 4034 section 6.1).
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.labels.iter().rev().cmp(other.labels.iter().rev())
     }
 }
 
 impl FromStr for Name {
-    type Err = Infallible;
+    type Err = ParseNameError;
 
+    /// Parses a Name from its presentation format.
+    ///
+    /// Relative names are treated as fully qualified. Names with non-ASCII
+    /// characters are converted to A-labels following UTS #46 (IDNA 2008
+    /// compatible processing).
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        let mut s = s.to_owned();
-        if !s.ends_with(".") {
-            s.push('.');
+        if s == "." {
+            return Ok(Self::root());
         }
-        let labels = if s == "." {
-            vec![Label::from_str("")]
-        } else {
-            s.split(".").map(|s| Label::from_str(s)).collect()
-        };
-        Ok(Self::from_labels(labels))
+
+        if !s.is_ascii() {
+            let ascii = idna::domain_to_ascii(s).map_err(|_| ParseNameError::BadIdn)?;
+            return Self::from_str(&ascii);
+        }
+
+        let mut texts = split_unescaped(s, '.');
+        if texts.last() == Some(&"") {
+            texts.pop();
+        }
+
+        let mut labels = texts
+            .into_iter()
+            .map(Label::from_text)
+            .collect::<Result<Vec<_>, _>>()?;
+        labels.push(Label(vec![]));
+        Self::from_labels(labels)
     }
 }
 
@@ -210,11 +486,9 @@ impl Display for Name {
             return write!(f, ".");
         }
 
-        for label in &self.labels {
-            write!(f, "{}", label.0)?;
-            if label.0 != "" {
-                write!(f, ".")?;
-            }
+        for label in self.labels.iter().filter(|l| !l.is_root()) {
+            label.fmt(f)?;
+            write!(f, ".")?;
         }
         Ok(())
     }
@@ -238,25 +512,16 @@ impl<'de> Deserialize<'de> for Name {
             where
                 E: serde::de::Error,
             {
-                let labels: Vec<_> = v.split(".").map(|s| Label::from_str(s)).collect();
-
-                match labels.last() {
-                    Some(label) if label.0 != "" => {
-                        return Err(serde::de::Error::invalid_value(
-                            serde::de::Unexpected::Str(v),
-                            &self,
-                        ));
-                    }
-                    None => {
-                        return Err(serde::de::Error::invalid_value(
-                            serde::de::Unexpected::Str(v),
-                            &self,
-                        ))
-                    }
-                    _ => {}
-                };
+                if !v.ends_with('.') {
+                    return Err(serde::de::Error::invalid_value(
+                        serde::de::Unexpected::Str(v),
+                        &self,
+                    ));
+                }
 
-                Ok(Name::from_labels(labels))
+                Name::from_str(v).map_err(|_| {
+                    serde::de::Error::invalid_value(serde::de::Unexpected::Str(v), &self)
+                })
             }
         }
 
@@ -295,11 +560,10 @@ impl<'a> Iterator for Suffixes<'a> {
             return None;
         }
 
-        let suffix = &self.name.labels[self.pos..];
-        let suffix: Vec<_> = suffix.iter().cloned().collect();
+        let suffix = self.name.labels[self.pos..].to_vec();
         self.pos += 1;
 
-        Some(Name::from_labels(suffix))
+        Some(Name { labels: suffix })
     }
 }
 
@@ -325,11 +589,10 @@ impl<'a> Iterator for Ancestors<'a> {
             return None;
         }
 
-        let ancestor = &self.name.labels[len - self.pos..];
-        let ancestor: Vec<_> = ancestor.iter().cloned().collect();
+        let ancestor = self.name.labels[len - self.pos..].to_vec();
         self.pos += 1;
 
-        Some(Name::from_labels(ancestor))
+        Some(Name { labels: ancestor })
     }
 }
 
@@ -454,11 +717,17 @@ pub enum Record {
         host: Name,
     },
     /// Text record.
+    ///
+    /// The content is a list of character strings. In zone files, the
+    /// content may be given as a single string, which is split into
+    /// character strings of 255 bytes, or as a list of strings. Strings are
+    /// escaped as in master files, e.g., `\"` or `\255`.
     Txt {
         name: Name,
         class: Class,
         ttl: u32,
-        content: String,
+        #[serde(deserialize_with = "CharacterString::deserialize_chunked")]
+        content: Vec<CharacterString>,
     },
     /// IPv6 address record.
     Aaaa {
@@ -467,6 +736,62 @@ pub enum Record {
         ttl: u32,
         addr: Ipv6Addr,
     },
+    /// Service location record.
+    Srv {
+        name: Name,
+        class: Class,
+        ttl: u32,
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: Name,
+    },
+    /// Delegation name record.
+    ///
+    /// Redirects the names below the owner (but not the owner itself) to the
+    /// corresponding names below the target.
+    Dname {
+        name: Name,
+        class: Class,
+        ttl: u32,
+        target: Name,
+    },
+    /// Certification authority authorization record.
+    Caa {
+        name: Name,
+        class: Class,
+        ttl: u32,
+        flags: u8,
+        tag: String,
+        value: String,
+    },
+    /// Service binding record.
+    ///
+    /// A priority of 0 indicates alias mode, in which the target is an alias
+    /// for the owner name. Otherwise the record is in service mode and
+    /// describes an endpoint for the service. A target of "." refers to the
+    /// owner name itself.
+    Svcb {
+        name: Name,
+        class: Class,
+        ttl: u32,
+        priority: u16,
+        target: Name,
+        #[serde(default)]
+        params: Vec<SvcParam>,
+    },
+    /// HTTPS service binding record.
+    ///
+    /// Identical in form to [`Record::Svcb`], for use with HTTPS origins.
+    Https {
+        name: Name,
+        class: Class,
+        ttl: u32,
+        priority: u16,
+        target: Name,
+        #[serde(default)]
+        params: Vec<SvcParam>,
+    },
     /// EDNS options record.
     Opt {
         name: Name,
@@ -476,80 +801,109 @@ pub enum Record {
         dnssec_ok: bool,
         data: Vec<u8>,
     },
+    /// Record of a type without dedicated support.
+    ///
+    /// The record data is kept as opaque bytes (RFC 3597). In zone files, the
+    /// type may be given as `TYPE<code>` (e.g., `TYPE1234`) and the data in
+    /// the generic `\# <length> <hex>` form.
+    Unknown {
+        name: Name,
+        class: Class,
+        ttl: u32,
+        r_type: u16,
+        #[serde(with = "generic_data")]
+        data: Vec<u8>,
+    },
 }
 
 impl Record {
     /// Creates a Record from a byte stream.
-    fn from_bytes(bytes: &mut Bytes) -> Self {
-        let name = Name::from_bytes(bytes);
-        let r_type = bytes.read_u16().unwrap();
-        let class = bytes.read_u16().unwrap();
-        let ttl = bytes.read_u32().unwrap();
-        let rd_len = bytes.read_u16().unwrap();
-
-        match r_type {
+    fn from_bytes(bytes: &mut Bytes) -> Result<Self, DecodeError> {
+        let name = Name::from_bytes(bytes)?;
+        let r_type = bytes.read_u16()?;
+        let class_offset = bytes.pos();
+        let class = bytes.read_u16()?;
+        let ttl = bytes.read_u32()?;
+        let rd_len = bytes.read_u16()? as usize;
+
+        let start = bytes.pos();
+        if bytes.remainder().len() < rd_len {
+            return Err(DecodeError::Truncated { offset: start });
+        }
+
+        // the class field of an OPT record carries the max response size
+        let max_response_size = class;
+        let class = match r_type {
+            41 => Class::Edns(class),
+            _ => Class::try_from(class).map_err(|class| DecodeError::UnknownClass {
+                offset: class_offset,
+                class,
+            })?,
+        };
+
+        let record = match r_type {
             1 => {
-                let addr = bytes.read_u32().unwrap().into();
+                let addr = bytes.read_u32()?.into();
 
                 Self::A {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     addr,
                 }
             }
             2 => {
-                let host = Name::from_bytes(bytes);
+                let host = Name::from_bytes(bytes)?;
 
                 Self::Ns {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     host,
                 }
             }
             3 => {
-                let host = Name::from_bytes(bytes);
+                let host = Name::from_bytes(bytes)?;
 
                 Self::Md {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     host,
                 }
             }
             4 => {
-                let host = Name::from_bytes(bytes);
+                let host = Name::from_bytes(bytes)?;
 
                 Self::Mf {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     host,
                 }
             }
             5 => {
-                let host = Name::from_bytes(bytes);
+                let host = Name::from_bytes(bytes)?;
 
                 Self::Cname {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     host,
                 }
             }
             6 => {
-                let origin = Name::from_bytes(bytes);
-                let mailbox = Name::from_bytes(bytes);
-                let version = bytes.read_u32().unwrap();
-                let refresh = bytes.read_u32().unwrap();
-                let retry = bytes.read_u32().unwrap();
-                let expire = bytes.read_u32().unwrap();
-                let minimum = bytes.read_u32().unwrap();
+                let origin = Name::from_bytes(bytes)?;
+                let mailbox = Name::from_bytes(bytes)?;
+                let version = bytes.read_u32()?;
+                let refresh = bytes.read_u32()?;
+                let retry = bytes.read_u32()?;
+                let expire = bytes.read_u32()?;
+                let minimum = bytes.read_u32()?;
 
                 Self::Soa {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     origin,
                     mailbox,
@@ -561,57 +915,62 @@ impl Record {
                 }
             }
             7 => {
-                let host = Name::from_bytes(bytes);
+                let host = Name::from_bytes(bytes)?;
 
                 Self::Mb {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     host,
                 }
             }
             8 => {
-                let host = Name::from_bytes(bytes);
+                let host = Name::from_bytes(bytes)?;
 
                 Self::Mg {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     host,
                 }
             }
             9 => {
-                let host = Name::from_bytes(bytes);
+                let host = Name::from_bytes(bytes)?;
 
                 Self::Mr {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     host,
                 }
             }
             10 => {
-                let data = bytes.read_exact(rd_len as usize).unwrap();
+                let data = bytes.read_exact(rd_len)?;
 
                 Self::Null {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     data,
                 }
             }
             11 => {
-                let addr = Ipv4Addr::from(bytes.read_u32().unwrap());
-                let protocol = bytes.read().unwrap();
+                let addr = Ipv4Addr::from(bytes.read_u32()?);
+                let protocol = bytes.read()?;
                 let data = {
-                    let len = rd_len as usize - 5;
-                    let bytez = bytes.read_exact(len).unwrap();
-                    bytez
+                    let len = rd_len
+                        .checked_sub(5)
+                        .ok_or(DecodeError::RdataLengthMismatch {
+                            offset: start,
+                            expected: rd_len,
+                            actual: 5,
+                        })?;
+                    bytes.read_exact(len)?
                 };
 
                 Self::Wks {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     addr,
                     protocol,
@@ -619,94 +978,153 @@ impl Record {
                 }
             }
             12 => {
-                let host = Name::from_bytes(bytes);
+                let host = Name::from_bytes(bytes)?;
 
                 Self::Ptr {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     host,
                 }
             }
             13 => {
-                let cpu = {
-                    let len = bytes.read().unwrap();
-                    let bytez = bytes.read_exact(len as usize).unwrap();
-                    String::from_utf8(bytez).unwrap()
-                };
-
-                let os = {
-                    let len = bytes.read().unwrap();
-                    let bytez = bytes.read_exact(len as usize).unwrap();
-                    String::from_utf8(bytez).unwrap()
-                };
+                let cpu = bytes.read_text()?;
+                let os = bytes.read_text()?;
 
                 Self::Hinfo {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     cpu,
                     os,
                 }
             }
             14 => {
-                let r_mailbox = Name::from_bytes(bytes);
-                let e_mailbox = Name::from_bytes(bytes);
+                let r_mailbox = Name::from_bytes(bytes)?;
+                let e_mailbox = Name::from_bytes(bytes)?;
 
                 Self::Minfo {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     r_mailbox,
                     e_mailbox,
                 }
             }
             15 => {
-                let priority = bytes.read_u16().unwrap();
-                let host = Name::from_bytes(bytes);
+                let priority = bytes.read_u16()?;
+                let host = Name::from_bytes(bytes)?;
 
                 Self::Mx {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     priority,
                     host,
                 }
             }
             16 => {
-                let content = {
-                    let mut buf = vec![];
-                    let mut read = 0;
-                    while read < rd_len {
-                        let len = bytes.read().unwrap();
-                        let bytez = bytes.read_exact(len as usize).unwrap();
-                        buf.extend(bytez);
-                        read += (len as u16) + 1;
-                    }
-                    String::from_utf8(buf).unwrap()
-                };
+                let mut content = vec![];
+                while bytes.pos() - start < rd_len {
+                    let len = bytes.read()?;
+                    let bytez = bytes.read_exact(len as usize)?;
+                    content.push(CharacterString(bytez));
+                }
 
                 Self::Txt {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     content,
                 }
             }
             28 => {
                 let addr = {
-                    let bytez = bytes.read_exact(16).unwrap();
+                    let bytez = bytes.read_exact(16)?;
                     let bytez: [u8; 16] = bytez.try_into().unwrap();
                     Ipv6Addr::from(bytez)
                 };
 
                 Self::Aaaa {
                     name,
-                    class: class.into(),
+                    class,
                     ttl,
                     addr,
                 }
             }
+            33 => {
+                let priority = bytes.read_u16()?;
+                let weight = bytes.read_u16()?;
+                let port = bytes.read_u16()?;
+                let target = Name::from_bytes(bytes)?;
+
+                Self::Srv {
+                    name,
+                    class,
+                    ttl,
+                    priority,
+                    weight,
+                    port,
+                    target,
+                }
+            }
+            39 => {
+                let target = Name::from_bytes(bytes)?;
+
+                Self::Dname {
+                    name,
+                    class,
+                    ttl,
+                    target,
+                }
+            }
+            257 => {
+                let flags = bytes.read()?;
+                let tag = bytes.read_text()?;
+                let value = {
+                    let offset = bytes.pos();
+                    let len = rd_len.saturating_sub(offset - start);
+                    let bytez = bytes.read_exact(len)?;
+                    String::from_utf8(bytez).map_err(|_| DecodeError::BadText { offset })?
+                };
+
+                Self::Caa {
+                    name,
+                    class,
+                    ttl,
+                    flags,
+                    tag,
+                    value,
+                }
+            }
+            64 | 65 => {
+                let priority = bytes.read_u16()?;
+                let target = Name::from_bytes(bytes)?;
+                let mut params = vec![];
+                while bytes.pos() - start < rd_len {
+                    params.push(SvcParam::from_bytes(bytes)?);
+                }
+
+                if r_type == 64 {
+                    Self::Svcb {
+                        name,
+                        class,
+                        ttl,
+                        priority,
+                        target,
+                        params,
+                    }
+                } else {
+                    Self::Https {
+                        name,
+                        class,
+                        ttl,
+                        priority,
+                        target,
+                        params,
+                    }
+                }
+            }
             41 => {
                 let (extended_rcode, version, dns_ok) = {
                     let bytez = ttl.to_be_bytes();
@@ -716,23 +1134,40 @@ impl Record {
                     (extended_rcode, version, dns_ok)
                 };
 
-                let data = {
-                    let len = rd_len as usize - 5;
-                    let bytez = bytes.read_exact(len).unwrap();
-                    bytez
-                };
+                let data = bytes.read_exact(rd_len)?;
 
                 Self::Opt {
                     name,
-                    max_response_size: class,
+                    max_response_size,
                     extended_rcode,
                     version,
                     dnssec_ok: dns_ok,
                     data,
                 }
             }
-            _ => panic!("unsupported record type: {r_type}"),
+            _ => {
+                let data = bytes.read_exact(rd_len)?;
+
+                Self::Unknown {
+                    name,
+                    class,
+                    ttl,
+                    r_type,
+                    data,
+                }
+            }
+        };
+
+        let read = bytes.pos() - start;
+        if read != rd_len {
+            return Err(DecodeError::RdataLengthMismatch {
+                offset: start,
+                expected: rd_len,
+                actual: read,
+            });
         }
+
+        Ok(record)
     }
 
     /// Returns a clone of this Record with a new name.
@@ -914,6 +1349,76 @@ impl Record {
                 ttl,
                 addr,
             },
+            Record::Srv {
+                class,
+                ttl,
+                priority,
+                weight,
+                port,
+                target,
+                ..
+            } => Record::Srv {
+                name,
+                class,
+                ttl,
+                priority,
+                weight,
+                port,
+                target,
+            },
+            Record::Dname {
+                class, ttl, target, ..
+            } => Record::Dname {
+                name,
+                class,
+                ttl,
+                target,
+            },
+            Record::Caa {
+                class,
+                ttl,
+                flags,
+                tag,
+                value,
+                ..
+            } => Record::Caa {
+                name,
+                class,
+                ttl,
+                flags,
+                tag,
+                value,
+            },
+            Record::Svcb {
+                class,
+                ttl,
+                priority,
+                target,
+                params,
+                ..
+            } => Record::Svcb {
+                name,
+                class,
+                ttl,
+                priority,
+                target,
+                params,
+            },
+            Record::Https {
+                class,
+                ttl,
+                priority,
+                target,
+                params,
+                ..
+            } => Record::Https {
+                name,
+                class,
+                ttl,
+                priority,
+                target,
+                params,
+            },
             Record::Opt {
                 max_response_size: class,
                 extended_rcode,
@@ -929,11 +1434,38 @@ impl Record {
                 dnssec_ok,
                 data,
             },
+            Record::Unknown {
+                class,
+                ttl,
+                r_type,
+                data,
+                ..
+            } => Record::Unknown {
+                name,
+                class,
+                ttl,
+                r_type,
+                data,
+            },
+        }
+    }
+
+    /// Converts an Unknown record whose type has dedicated support into the
+    /// corresponding typed record.
+    ///
+    /// Other records are returned unchanged.
+    fn into_known(self) -> Result<Self, DecodeError> {
+        if !matches!(self, Record::Unknown { .. }) {
+            return Ok(self);
         }
+        let mut bytes = Bytes::new();
+        self.to_bytes(&mut bytes);
+        let mut bytes = Bytes::from_buf(bytes.used());
+        Record::from_bytes(&mut bytes)
     }
 
     /// Returns the name of the record.
-    fn name(&self) -> &Name {
+    pub fn name(&self) -> &Name {
         match self {
             Record::A { name, .. } => name,
             Record::Ns { name, .. } => name,
@@ -952,7 +1484,13 @@ impl Record {
             Record::Mx { name, .. } => name,
             Record::Txt { name, .. } => name,
             Record::Aaaa { name, .. } => name,
+            Record::Srv { name, .. } => name,
+            Record::Dname { name, .. } => name,
+            Record::Caa { name, .. } => name,
+            Record::Svcb { name, .. } => name,
+            Record::Https { name, .. } => name,
             Record::Opt { name, .. } => name,
+            Record::Unknown { name, .. } => name,
         }
     }
 
@@ -976,6 +1514,12 @@ impl Record {
             Record::Mx { class, .. } => class.clone(),
             Record::Txt { class, .. } => class.clone(),
             Record::Aaaa { class, .. } => class.clone(),
+            Record::Srv { class, .. } => class.clone(),
+            Record::Dname { class, .. } => class.clone(),
+            Record::Caa { class, .. } => class.clone(),
+            Record::Svcb { class, .. } => class.clone(),
+            Record::Https { class, .. } => class.clone(),
+            Record::Unknown { class, .. } => class.clone(),
             Record::Opt {
                 max_response_size, ..
             } => Class::Edns(*max_response_size),
@@ -1002,6 +1546,12 @@ impl Record {
             Record::Mx { ttl, .. } => *ttl,
             Record::Txt { ttl, .. } => *ttl,
             Record::Aaaa { ttl, .. } => *ttl,
+            Record::Srv { ttl, .. } => *ttl,
+            Record::Dname { ttl, .. } => *ttl,
+            Record::Caa { ttl, .. } => *ttl,
+            Record::Svcb { ttl, .. } => *ttl,
+            Record::Https { ttl, .. } => *ttl,
+            Record::Unknown { ttl, .. } => *ttl,
             Record::Opt {
                 extended_rcode,
                 version,
@@ -1031,7 +1581,13 @@ impl Record {
             Record::Mx { .. } => 15,
             Record::Txt { .. } => 16,
             Record::Aaaa { .. } => 28,
+            Record::Srv { .. } => 33,
+            Record::Dname { .. } => 39,
+            Record::Caa { .. } => 257,
+            Record::Svcb { .. } => 64,
+            Record::Https { .. } => 65,
             Record::Opt { .. } => 41,
+            Record::Unknown { r_type, .. } => *r_type,
         }
     }
 
@@ -1203,11 +1759,9 @@ impl Record {
                 let pos = bytes.pos();
                 bytes.write_u16(0);
 
-                let bytez = content.as_bytes();
-                let chunks = bytez.chunks(255);
-                for chunk in chunks {
-                    bytes.write(chunk.len() as u8);
-                    bytes.write_all(chunk);
+                for string in content {
+                    bytes.write(string.0.len() as u8);
+                    bytes.write_all(&string.0);
                 }
 
                 let size = bytes.pos() - (pos + 2);
@@ -1217,8 +1771,82 @@ impl Record {
                 bytes.write_u16(16);
                 bytes.write_all(&addr.octets());
             }
-            Record::Opt { data, .. } => {
-                bytes.write_u16(data.len() as u16);
+            Record::Srv {
+                priority,
+                weight,
+                port,
+                target,
+                ..
+            } => {
+                let pos = bytes.pos();
+                bytes.write_u16(0);
+
+                bytes.write_u16(*priority);
+                bytes.write_u16(*weight);
+                bytes.write_u16(*port);
+                target.to_bytes_uncompressed(bytes);
+
+                let size = bytes.pos() - (pos + 2);
+                bytes.set_u16(pos, size as u16);
+            }
+            Record::Dname { target, .. } => {
+                let pos = bytes.pos();
+                bytes.write_u16(0);
+
+                // the target of a DNAME record must not be compressed
+                target.to_bytes_uncompressed(bytes);
+
+                let size = bytes.pos() - (pos + 2);
+                bytes.set_u16(pos, size as u16);
+            }
+            Record::Caa {
+                flags, tag, value, ..
+            } => {
+                let pos = bytes.pos();
+                bytes.write_u16(0);
+
+                bytes.write(*flags);
+                bytes.write(tag.len() as u8);
+                bytes.write_all(tag.as_bytes());
+                bytes.write_all(value.as_bytes());
+
+                let size = bytes.pos() - (pos + 2);
+                bytes.set_u16(pos, size as u16);
+            }
+            Record::Svcb {
+                priority,
+                target,
+                params,
+                ..
+            }
+            | Record::Https {
+                priority,
+                target,
+                params,
+                ..
+            } => {
+                let pos = bytes.pos();
+                bytes.write_u16(0);
+
+                bytes.write_u16(*priority);
+                target.to_bytes_uncompressed(bytes);
+
+                // parameters must appear in increasing order of key
+                let mut params: Vec<_> = params.iter().collect();
+                params.sort_by_key(|p| p.key());
+                for param in params {
+                    param.to_bytes(bytes);
+                }
+
+                let size = bytes.pos() - (pos + 2);
+                bytes.set_u16(pos, size as u16);
+            }
+            Record::Opt { data, .. } => {
+                bytes.write_u16(data.len() as u16);
+                bytes.write_all(data)
+            }
+            Record::Unknown { data, .. } => {
+                bytes.write_u16(data.len() as u16);
                 bytes.write_all(data)
             }
         }
@@ -1226,14 +1854,43 @@ impl Record {
 }
 
 impl Display for Record {
+    /// Formats the record in presentation format. The alternate form (`{:#}`)
+    /// shows names with Unicode labels (see [`Name`]).
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {} {} ", self.name(), self.class(), self.ttl())?;
-        match self {
+        self.name().fmt(f)?;
+        write!(f, " {} {} ", self.class(), self.ttl())?;
+        RecordData(self).fmt(f)
+    }
+}
+
+/// The type and data of a record in presentation format.
+///
+/// This is the part of a record that follows the owner, class and TTL, e.g.,
+/// `MX 10 mail.example.com.`.
+struct RecordData<'a>(&'a Record);
+
+impl Display for RecordData<'_> {
+    /// Names are written with the same formatter, so the alternate form
+    /// (`{:#}`) shows them with Unicode labels (see [`Name`]).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
             Record::A { addr, .. } => write!(f, "A {addr}"),
-            Record::Ns { host, .. } => write!(f, "NS {host}"),
-            Record::Md { host, .. } => write!(f, "MD {host}"),
-            Record::Mf { host, .. } => write!(f, "MF {host}"),
-            Record::Cname { host, .. } => write!(f, "CNAME {host}"),
+            Record::Ns { host, .. } => {
+                write!(f, "NS ")?;
+                host.fmt(f)
+            }
+            Record::Md { host, .. } => {
+                write!(f, "MD ")?;
+                host.fmt(f)
+            }
+            Record::Mf { host, .. } => {
+                write!(f, "MF ")?;
+                host.fmt(f)
+            }
+            Record::Cname { host, .. } => {
+                write!(f, "CNAME ")?;
+                host.fmt(f)
+            }
             Record::Soa {
                 origin,
                 mailbox,
@@ -1243,35 +1900,280 @@ impl Display for Record {
                 expire,
                 minimum,
                 ..
-            } => write!(
-                f,
-                "SOA {origin} {mailbox} {version} {refresh} {retry} {expire} {minimum}"
-            ),
-            Record::Mb { host, .. } => write!(f, "MB {host}"),
-            Record::Mg { host, .. } => write!(f, "MG {host}"),
-            Record::Mr { host, .. } => write!(f, "MR {host}"),
-            Record::Null { data, .. } => write!(f, "NULL {data:x?}"),
+            } => {
+                write!(f, "SOA ")?;
+                origin.fmt(f)?;
+                write!(f, " ")?;
+                mailbox.fmt(f)?;
+                write!(f, " {version} {refresh} {retry} {expire} {minimum}")
+            }
+            Record::Mb { host, .. } => {
+                write!(f, "MB ")?;
+                host.fmt(f)
+            }
+            Record::Mg { host, .. } => {
+                write!(f, "MG ")?;
+                host.fmt(f)
+            }
+            Record::Mr { host, .. } => {
+                write!(f, "MR ")?;
+                host.fmt(f)
+            }
+            Record::Null { data, .. } => write!(f, "NULL {}", generic_data::to_string(data)),
             Record::Wks {
                 addr,
                 protocol,
                 data,
                 ..
-            } => write!(f, "WKS {addr} {protocol} {data:x?}"),
-            Record::Ptr { host, .. } => write!(f, "PTR {host}"),
-            Record::Hinfo { cpu, os, .. } => write!(f, "HINFO {cpu} {os}"),
+            } => {
+                write!(f, "WKS {addr} {protocol}")?;
+                for (i, byte) in data.iter().enumerate() {
+                    for bit in 0..8 {
+                        if byte & (0x80 >> bit) != 0 {
+                            write!(f, " {}", i * 8 + bit)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Record::Ptr { host, .. } => {
+                write!(f, "PTR ")?;
+                host.fmt(f)
+            }
+            Record::Hinfo { cpu, os, .. } => write!(
+                f,
+                "HINFO {} {}",
+                CharacterString(cpu.as_bytes().to_vec()),
+                CharacterString(os.as_bytes().to_vec())
+            ),
             Record::Minfo {
                 r_mailbox,
                 e_mailbox,
                 ..
-            } => write!(f, "MINFO {r_mailbox} {e_mailbox}"),
-            Record::Mx { priority, host, .. } => write!(f, "MX {priority} {host}"),
-            Record::Txt { content, .. } => write!(f, "TXT {content}"),
+            } => {
+                write!(f, "MINFO ")?;
+                r_mailbox.fmt(f)?;
+                write!(f, " ")?;
+                e_mailbox.fmt(f)
+            }
+            Record::Mx { priority, host, .. } => {
+                write!(f, "MX {priority} ")?;
+                host.fmt(f)
+            }
+            Record::Txt { content, .. } => {
+                write!(f, "TXT")?;
+                content.iter().try_for_each(|s| write!(f, " {s}"))
+            }
             Record::Aaaa { addr, .. } => write!(f, "AAAA {addr}"),
+            Record::Srv {
+                priority,
+                weight,
+                port,
+                target,
+                ..
+            } => {
+                write!(f, "SRV {priority} {weight} {port} ")?;
+                target.fmt(f)
+            }
+            Record::Dname { target, .. } => {
+                write!(f, "DNAME ")?;
+                target.fmt(f)
+            }
+            Record::Caa {
+                flags, tag, value, ..
+            } => write!(
+                f,
+                "CAA {flags} {tag} {}",
+                CharacterString::quote(value.as_bytes())
+            ),
+            Record::Svcb {
+                priority,
+                target,
+                params,
+                ..
+            } => {
+                write!(f, "SVCB {priority} ")?;
+                target.fmt(f)?;
+                params.iter().try_for_each(|p| write!(f, " {p}"))
+            }
+            Record::Https {
+                priority,
+                target,
+                params,
+                ..
+            } => {
+                write!(f, "HTTPS {priority} ")?;
+                target.fmt(f)?;
+                params.iter().try_for_each(|p| write!(f, " {p}"))
+            }
             Record::Opt { data, .. } => write!(f, "OPT {data:x?}"),
+            Record::Unknown { r_type, data, .. } => {
+                write!(f, "TYPE{r_type} {}", generic_data::to_string(data))
+            }
         }
     }
 }
 
+/// A character string of up to 255 bytes.
+///
+/// Character strings may contain arbitrary bytes. They are displayed as
+/// quoted strings, in which quotes and backslashes are escaped with a
+/// backslash and other non-printable bytes are escaped as `\DDD`, where
+/// `DDD` is the decimal value of the byte. They are serialized as the same
+/// text without the surrounding quotes.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CharacterString(Vec<u8>);
+
+impl CharacterString {
+    /// Splits bytes into character strings of at most 255 bytes.
+    ///
+    /// Empty input produces a single empty character string.
+    pub fn chunked(bytes: &[u8]) -> Vec<Self> {
+        if bytes.is_empty() {
+            return vec![Self(vec![])];
+        }
+        bytes.chunks(255).map(|c| Self(c.to_vec())).collect()
+    }
+
+    /// Returns the bytes of the character string.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Formats bytes of any length as a quoted and escaped string, like a
+    /// character string, e.g., for the value of a CAA record.
+    pub fn quote(bytes: &[u8]) -> String {
+        Self(bytes.to_vec()).to_string()
+    }
+
+    /// Returns the escaped text of the character string, without quotes.
+    fn escaped(&self) -> String {
+        let mut text = String::new();
+        for byte in &self.0 {
+            match byte {
+                b'"' | b'\\' => {
+                    text.push('\\');
+                    text.push(*byte as char);
+                }
+                b' '..=b'~' => text.push(*byte as char),
+                _ => text.push_str(&format!("\\{byte:03}")),
+            }
+        }
+        text
+    }
+
+    /// Deserializes a list of character strings from either a single string,
+    /// which is split into chunks, or a list of strings.
+    fn deserialize_chunked<'de, D>(deserializer: D) -> std::result::Result<Vec<Self>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Content {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        let unescape = |text: &str| {
+            crate::unescape(text).ok_or_else(|| {
+                serde::de::Error::invalid_value(
+                    serde::de::Unexpected::Str(text),
+                    &"a string with valid escapes",
+                )
+            })
+        };
+
+        match Content::deserialize(deserializer)? {
+            Content::One(text) => Ok(Self::chunked(&unescape(&text)?)),
+            Content::Many(texts) => texts
+                .iter()
+                .map(|text| match unescape(text)? {
+                    bytes if bytes.len() <= 255 => Ok(Self(bytes)),
+                    bytes => Err(serde::de::Error::invalid_length(
+                        bytes.len(),
+                        &"a string of at most 255 bytes",
+                    )),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Display for CharacterString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"{}\"", self.escaped())
+    }
+}
+
+impl Serialize for CharacterString {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.escaped())
+    }
+}
+
+/// Serialization of opaque record data in the generic RFC 3597 format.
+///
+/// The data is represented as `\# <length> <hex>`, e.g., `\# 4 0a000001`.
+/// Empty data is represented as `\# 0`.
+pub mod generic_data {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Formats data in the generic format.
+    pub fn to_string(data: &[u8]) -> String {
+        let mut text = format!("\\# {}", data.len());
+        if !data.is_empty() {
+            text.push(' ');
+            for byte in data {
+                text.push_str(&format!("{byte:02x}"));
+            }
+        }
+        text
+    }
+
+    /// Parses data in the generic format.
+    ///
+    /// Whitespace is permitted between hex digits.
+    pub fn from_str(text: &str) -> Option<Vec<u8>> {
+        let mut parts = text.split_whitespace();
+        if parts.next()? != "\\#" {
+            return None;
+        }
+        let len: usize = parts.next()?.parse().ok()?;
+        let hex: String = parts.collect();
+        if hex.len() != len * 2 || !hex.is_ascii() {
+            return None;
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect()
+    }
+
+    pub fn serialize<S>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&to_string(data))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        from_str(&text).ok_or_else(|| {
+            serde::de::Error::invalid_value(
+                serde::de::Unexpected::Str(&text),
+                &"generic record data (\\# <length> <hex>)",
+            )
+        })
+    }
+}
+
 /// DNS record class.
 #[derive(Default, Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "UPPERCASE")]
@@ -1302,14 +2204,17 @@ impl Display for Class {
     }
 }
 
-impl From<u16> for Class {
-    fn from(value: u16) -> Self {
+impl TryFrom<u16> for Class {
+    type Error = u16;
+
+    /// Returns the unrecognized value as the error.
+    fn try_from(value: u16) -> std::result::Result<Self, Self::Error> {
         match value {
-            1 => Class::In,
-            2 => Class::Cs,
-            3 => Class::Ch,
-            4 => Class::Hs,
-            _ => panic!("unsupported class: {value}"),
+            1 => Ok(Class::In),
+            2 => Ok(Class::Cs),
+            3 => Ok(Class::Ch),
+            4 => Ok(Class::Hs),
+            _ => Err(value),
         }
     }
 }
@@ -1329,16 +2234,36 @@ impl From<Class> for u16 {
 /// A subset of the DNS namespace.
 ///
 /// This usually represents a single domain.
-#[derive(Deserialize)]
 pub struct Zone {
     /// Name of the zone.
-    #[serde(rename = "name")]
-    _name: Name,
-    /// Records in the zone.
+    name: Name,
+    /// Records in the zone, grouped by name in canonical order.
+    records: BTreeMap<Name, Vec<Record>>,
+}
+
+/// A zone as it is written in TOML format.
+#[derive(Deserialize)]
+struct ZoneFile {
+    name: Name,
     records: Vec<Record>,
 }
 
 impl Zone {
+    /// Creates a new Zone from a list of records.
+    ///
+    /// The records of each name keep their order in the list.
+    fn new(name: Name, records: Vec<Record>) -> Self {
+        let mut index: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for record in records {
+            index.entry(record.name().clone()).or_default().push(record);
+        }
+
+        Self {
+            name,
+            records: index,
+        }
+    }
+
     /// Parse a Zone from an input text in TOML format.
     ///
     /// The input should contain a `records` list with one record per item.
@@ -1353,6 +2278,11 @@ impl Zone {
     /// record type. For more information on expected fields for each type,
     /// refer to the [`Record`] documentation.
     ///
+    /// Records of any type may also be given in the generic format of RFC
+    /// 3597, with a `type` of `TYPE<code>` and a `data` field in the form
+    /// `\# <length> <hex>`. Generic records of a supported type are converted
+    /// to the corresponding typed record.
+    ///
     /// # Example
     ///
     /// The following example defines a zone with one address record.
@@ -1366,14 +2296,378 @@ impl Zone {
     /// addr = "0.0.0.0"
     /// ```
     pub fn from_toml(input: &str) -> Result<Self> {
-        let zone = toml::from_str(input)?;
+        let mut table: toml::Table = toml::from_str(input)?;
+
+        // rewrite generic types (TYPE<code>) so that they deserialize as
+        // unknown records
+        if let Some(toml::Value::Array(records)) = table.get_mut("records") {
+            for record in records.iter_mut().filter_map(|r| r.as_table_mut()) {
+                let code = record
+                    .get("type")
+                    .and_then(|t| t.as_str())
+                    .and_then(|t| t.strip_prefix("TYPE"))
+                    .and_then(|c| c.parse::<u16>().ok());
+
+                if let Some(code) = code {
+                    record.insert("type".into(), "UNKNOWN".into());
+                    record.insert("r_type".into(), i64::from(code).into());
+                }
+            }
+        }
+
+        let file: ZoneFile = table.try_into()?;
+        let records = file
+            .records
+            .into_iter()
+            .map(Record::into_known)
+            .collect::<Result<_, _>>()?;
+
+        let zone = Self::new(file.name, records);
+        zone.validate()?;
+        Ok(zone)
+    }
+
+    /// Parse a Zone from an input text in master file format (RFC 1035).
+    ///
+    /// Relative names are resolved against `origin` until it is changed by an
+    /// `$ORIGIN` directive. The `$TTL`, `$INCLUDE` and `$GENERATE` directives
+    /// are supported, as are `@`, omitted owners, TTLs and classes (which are
+    /// inherited from the previous record), parentheses, comments and escaped
+    /// characters. Records of any type may also be given in the generic
+    /// format of RFC 3597.
+    ///
+    /// The name of the zone is the owner of its SOA record, or `origin` if
+    /// the zone has no SOA record. `$INCLUDE` paths are resolved against the
+    /// current directory.
+    ///
+    /// # Example
+    ///
+    /// ```text
+    /// $ORIGIN example.com.
+    /// $TTL 3600
+    /// @    IN SOA ns admin 1 7200 3600 1209600 300
+    ///      IN NS  ns
+    /// ns   IN A   192.0.2.1
+    /// ```
+    pub fn from_master(input: &str, origin: Option<Name>) -> Result<Self> {
+        let zone = master::parse(input, origin, Path::new("."))?;
+        zone.validate()?;
+        Ok(zone)
+    }
+
+    /// Load a Zone from a file.
+    ///
+    /// Files with a `.toml` extension are parsed as TOML (see
+    /// [`Zone::from_toml`]). All other files are parsed in master file format
+    /// (see [`Zone::from_master`]), with relative names resolved against
+    /// `origin` and `$INCLUDE` paths resolved against the directory of the
+    /// file. TOML files name their zone themselves, so `origin` is only used
+    /// for master files.
+    pub fn from_file(path: impl AsRef<Path>, origin: Option<Name>) -> Result<Self> {
+        let path = path.as_ref();
+        let input = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+
+        if path.extension().is_some_and(|e| e == "toml") {
+            return Self::from_toml(&input);
+        }
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let zone = master::parse(&input, origin, dir)?;
+        zone.validate()?;
         Ok(zone)
     }
 
+    /// Writes the Zone in canonical master file form.
+    ///
+    /// Every record is written with a fully qualified owner, an explicit TTL
+    /// and an explicit class, so the output does not depend on any directives.
+    /// Records are written in canonical order of their names, except for the
+    /// SOA record, which comes first.
+    pub fn to_master(&self) -> String {
+        master::write(self)
+    }
+
+    /// Checks that the records in the zone are well formed and that their
+    /// owners are inside the zone.
+    fn validate(&self) -> Result<()> {
+        for record in self.records() {
+            if !record.name().is_subdomain_of(&self.name) {
+                bail!("record {} is outside the zone {}", record.name(), self.name);
+            }
+
+            if let Record::Caa { name, tag, .. } = record {
+                let is_known = ["issue", "issuewild", "iodef"]
+                    .iter()
+                    .any(|t| t.eq_ignore_ascii_case(tag));
+                let is_valid = !tag.is_empty()
+                    && tag.len() <= 15
+                    && tag.chars().all(|c| c.is_ascii_alphanumeric());
+                if !is_known && !is_valid {
+                    bail!("invalid CAA tag for {name}: {tag:?} (must be alphanumeric)");
+                }
+            }
+
+            if let Record::Svcb { name, params, .. } | Record::Https { name, params, .. } = record {
+                svcb::validate(params)
+                    .with_context(|| format!("invalid service parameters for {name}"))?;
+            }
+
+            // the targets of these records must be host names (RFC 1035 and
+            // RFC 2782), unless they are the root name, which means that
+            // there is no service (RFC 7505)
+            if let Record::Ns { name, host, .. }
+            | Record::Mx { name, host, .. }
+            | Record::Srv {
+                name, target: host, ..
+            } = record
+            {
+                if !host.is_root() && !host.is_hostname() {
+                    bail!("invalid target for {name}: {host} is not a valid host name");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the zone.
+    pub fn name(&self) -> &Name {
+        &self.name
+    }
+
+    /// Returns the records in the zone, in canonical order of their names.
+    pub fn records(&self) -> impl Iterator<Item = &Record> {
+        self.records.values().flatten()
+    }
+
+    /// Returns the SOA record at the apex of the zone, if there is one.
+    pub fn soa(&self) -> Option<&Record> {
+        self.find_with_name(&self.name)
+            .into_iter()
+            .find(|r| matches!(r, Record::Soa { .. }))
+    }
+
     /// Returns records with the specified name.
+    ///
+    /// Names are compared case-insensitively.
     pub fn find_with_name(&self, name: &Name) -> Vec<&Record> {
-        self.records.iter().filter(|r| r.name() == name).collect()
+        self.records
+            .get(name)
+            .map(|records| records.iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns true if a name exists in the zone.
+    ///
+    /// A name exists if it owns records or if it is an empty non-terminal,
+    /// that is, a name without records of its own whose descendants own
+    /// records.
+    pub fn contains_name(&self, name: &Name) -> bool {
+        // descendants directly follow a name in canonical order
+        self.records
+            .range(name..)
+            .next()
+            .is_some_and(|(n, _)| n.is_subdomain_of(name))
+    }
+
+    /// Returns the closest encloser of a name (RFC 4592), that is, the
+    /// longest ancestor of the name (or the name itself) that exists in the
+    /// zone.
+    ///
+    /// Returns None if the name is not in the zone.
+    pub fn closest_encloser(&self, name: &Name) -> Option<Name> {
+        if !name.is_subdomain_of(&self.name) {
+            return None;
+        }
+
+        // names below a name that does not exist do not exist either
+        name.ancestors()
+            .filter(|a| a.is_subdomain_of(&self.name))
+            .take_while(|a| self.contains_name(a))
+            .last()
+    }
+
+    /// Returns the records of the wildcard that can answer for a name, that
+    /// is, those of the wildcard child of its closest encloser (RFC 4592).
+    ///
+    /// Returns an empty list if there is no such wildcard or if the name
+    /// itself exists in the zone.
+    pub fn find_wildcard(&self, name: &Name) -> Vec<&Record> {
+        match self.closest_encloser(name) {
+            Some(encloser) if &encloser != name => encloser
+                .to_child_wildcard()
+                .map(|wildcard| self.find_with_name(&wildcard))
+                .unwrap_or_default(),
+            _ => vec![],
+        }
     }
+
+    /// Returns the name that owns records and precedes a name in canonical
+    /// order (RFC 4034), if any.
+    ///
+    /// The name itself need not exist in the zone. Returns None for the apex
+    /// and any name before it, without wrapping around.
+    pub fn predecessor(&self, name: &Name) -> Option<&Name> {
+        self.records.range(..name).next_back().map(|(name, _)| name)
+    }
+
+    /// Returns the name that owns records and follows a name in canonical
+    /// order (RFC 4034), if any.
+    ///
+    /// The name itself need not exist in the zone. Returns None for the last
+    /// name in the zone, without wrapping around.
+    pub fn successor(&self, name: &Name) -> Option<&Name> {
+        self.records
+            .range((Bound::Excluded(name), Bound::Unbounded))
+            .next()
+            .map(|(name, _)| name)
+    }
+
+    /// Looks up the records for a name and type in the zone.
+    ///
+    /// Zone cuts and DNAME records above the name take precedence over its
+    /// own records, and a name that does not exist is answered from the
+    /// wildcard at its closest encloser, if any (RFC 1034 section 4.3.2 and
+    /// RFC 4592).
+    pub fn lookup(&self, name: &Name, q_type: &QuestionType) -> Lookup {
+        // names below the closest encloser do not exist, so any zone cut or
+        // DNAME record that applies to the name is at or above it
+        let Some(encloser) = self.closest_encloser(name) else {
+            return Lookup::NameError;
+        };
+
+        for qname in encloser.ancestors() {
+            if !qname.is_subdomain_of(&self.name) {
+                continue;
+            }
+            let name_records = self.find_with_name(&qname);
+
+            // leaf or ancestor: check for delegation (name servers at the
+            // apex are authoritative for the zone itself), which also covers
+            // the records at the zone cut (RFC 1034 section 4.3.2, step 3b)
+            let delegation_records: Vec<_> = name_records
+                .iter()
+                .filter(|r| matches!(r, Record::Ns { .. }))
+                .map(|r| (*r).clone())
+                .collect();
+
+            if !delegation_records.is_empty() && qname != self.name {
+                return Lookup::Referral(delegation_records);
+            }
+
+            // leaf: check for matching records
+            if &qname == name {
+                if let Some(lookup) = match_records(name, &name_records, q_type) {
+                    return lookup;
+                }
+            }
+
+            // ancestor: check for a DNAME record, which redirects the names
+            // below its owner
+            let dname = name_records
+                .iter()
+                .find(|r| matches!(r, Record::Dname { .. }));
+
+            if let Some(&dname) = dname.filter(|_| &qname != name) {
+                let Record::Dname {
+                    class, ttl, target, ..
+                } = dname
+                else {
+                    unreachable!()
+                };
+
+                return match name.replace_suffix(&qname, target) {
+                    Some(host) => {
+                        let cname = Record::Cname {
+                            name: name.clone(),
+                            class: class.clone(),
+                            ttl: *ttl,
+                            host,
+                        };
+                        Lookup::Alias(vec![dname.clone(), cname])
+                    }
+                    None => Lookup::NameExists(dname.clone()),
+                };
+            }
+        }
+
+        // the name exists but has no records of the requested type
+        if &encloser == name {
+            return Lookup::NoData;
+        }
+
+        let records = self.find_wildcard(name);
+        if records.is_empty() {
+            return Lookup::NameError;
+        }
+        match_records(name, &records, q_type).unwrap_or(Lookup::NoData)
+    }
+}
+
+/// Outcome of looking up a name and type in a zone.
+#[derive(Debug, PartialEq)]
+pub enum Lookup {
+    /// Records of the requested type.
+    Answer(Vec<Record>),
+    /// A CNAME record for the name, which has to be followed to find records
+    /// of the requested type.
+    ///
+    /// The CNAME record comes last. It is preceded by the DNAME record that
+    /// it was synthesized from, if any.
+    Alias(Vec<Record>),
+    /// A DNAME record applies to the name, but the name that it substitutes
+    /// would be too long.
+    NameExists(Record),
+    /// Name server records of a zone that the name was delegated to.
+    Referral(Vec<Record>),
+    /// The name exists but has no records of the requested type.
+    NoData,
+    /// The name does not exist.
+    NameError,
+}
+
+/// Matches the records of a name (or of the wildcard that it matches) against
+/// a requested type.
+///
+/// The records returned are owned by the name. Returns None if no records
+/// match.
+fn match_records(name: &Name, records: &[&Record], q_type: &QuestionType) -> Option<Lookup> {
+    if let Some(cname) = records.iter().find(|r| matches!(r, Record::Cname { .. })) {
+        let cname = cname.with_name(name.clone());
+        return match q_type {
+            QuestionType::CNAME | QuestionType::ALL => Some(Lookup::Answer(vec![cname])),
+            _ => Some(Lookup::Alias(vec![cname])),
+        };
+    }
+
+    let mut matched_records: Vec<_> = records
+        .iter()
+        .filter(|r| r.code() == q_type.code() || matches!(q_type, QuestionType::ALL))
+        .collect();
+
+    // service bindings in alias mode take precedence over those in service
+    // mode at the same name
+    let is_alias = |r: &&&Record| {
+        matches!(
+            r,
+            Record::Svcb { priority: 0, .. } | Record::Https { priority: 0, .. }
+        )
+    };
+    if matches!(q_type, QuestionType::SVCB | QuestionType::HTTPS)
+        && matched_records.iter().any(is_alias)
+    {
+        matched_records.retain(is_alias);
+    }
+
+    if matched_records.is_empty() {
+        return None;
+    }
+
+    let records = matched_records
+        .into_iter()
+        .map(|r| r.with_name(name.clone()))
+        .collect();
+    Some(Lookup::Answer(records))
 }
 
 /// A DNS message.
@@ -1392,59 +2686,91 @@ impl Message {
         Default::default()
     }
 
+    /// Returns true if this message is a response to a request, that is, if
+    /// it is a response with the same ID that echoes the questions of the
+    /// request.
+    ///
+    /// Responses with a format error or not implemented response code may
+    /// omit the questions, since the server may not have been able to parse
+    /// them.
+    pub fn is_response_to(&self, request: &Message) -> bool {
+        let is_error = matches!(
+            self.header.resp_code,
+            ResponseCode::FormatError | ResponseCode::NotImplemented
+        );
+
+        self.header.is_response
+            && self.header.id == request.header.id
+            && (self.questions == request.questions || is_error && self.questions.is_empty())
+    }
+
     /// Creates a Message from a byte stream.
-    pub fn from_bytes(bytes: &mut Bytes) -> Self {
-        let header = Header::from_bytes(bytes);
+    ///
+    /// Returns an error if the byte stream does not contain a well-formed
+    /// message.
+    pub fn from_bytes(bytes: &mut Bytes) -> Result<Self, DecodeError> {
+        let header = Header::from_bytes(bytes)?;
 
-        let questions: Vec<_> = (0..header.question_count)
+        let questions = (0..header.question_count)
             .map(|_| Question::from_bytes(bytes))
-            .collect();
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let answer_records: Vec<_> = (0..header.answer_count)
+        let answer_records = (0..header.answer_count)
             .map(|_| Record::from_bytes(bytes))
-            .collect();
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let authority_records: Vec<_> = (0..header.authority_count)
+        let authority_records = (0..header.authority_count)
             .map(|_| Record::from_bytes(bytes))
-            .collect();
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let additional_records: Vec<_> = (0..header.additional_count)
+        let additional_records = (0..header.additional_count)
             .map(|_| Record::from_bytes(bytes))
-            .collect();
+            .collect::<Result<Vec<_>, _>>()?;
 
-        Self {
+        Ok(Self {
             header,
             questions,
             answer_records,
             authority_records,
             additional_records,
-        }
+        })
     }
 
     /// Converts a Message to a byte stream.
     pub fn to_bytes(&self, bytes: &mut Bytes) {
+        self.to_bytes_with_offsets(bytes);
+    }
+
+    /// Converts a Message to a byte stream, returning the offset at which
+    /// each record starts, in the order of the sections.
+    ///
+    /// Names are only compressed against names that precede them, so the
+    /// records before any of these offsets are encoded the same way in a
+    /// message that has only those records.
+    pub fn to_bytes_with_offsets(&self, bytes: &mut Bytes) -> Vec<usize> {
         self.header.to_bytes(bytes);
 
         for question in &self.questions {
             question.to_bytes(bytes);
         }
 
-        for record in &self.answer_records {
-            record.to_bytes(bytes);
-        }
-
-        for record in &self.authority_records {
-            record.to_bytes(bytes);
-        }
+        let records = self
+            .answer_records
+            .iter()
+            .chain(&self.authority_records)
+            .chain(&self.additional_records);
 
-        for record in &self.additional_records {
+        let mut offsets = vec![];
+        for record in records {
+            offsets.push(bytes.used().len());
             record.to_bytes(bytes);
         }
+        offsets
     }
 }
 
 /// A DNS operation code.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
 pub enum OperationCode {
     /// A standard query.
     Query,
@@ -1452,6 +2778,9 @@ pub enum OperationCode {
     InverseQuery,
     /// A server status request.
     Status,
+    /// An operation code that is not supported, e.g., NOTIFY (4) or UPDATE
+    /// (5).
+    Unknown(u8),
 }
 
 impl From<u8> for OperationCode {
@@ -1462,7 +2791,7 @@ impl From<u8> for OperationCode {
             0 => Query,
             1 => InverseQuery,
             2 => Status,
-            _ => panic!("unsupported operation code: {value}"),
+            _ => Unknown(value),
         }
     }
 }
@@ -1475,6 +2804,7 @@ impl From<OperationCode> for u8 {
             Query => 0,
             InverseQuery => 1,
             Status => 2,
+            Unknown(code) => code & 0b1111,
         }
     }
 }
@@ -1487,12 +2817,13 @@ impl Display for OperationCode {
             Query => write!(f, "query"),
             InverseQuery => write!(f, "inverse"),
             Status => write!(f, "status"),
+            Unknown(code) => write!(f, "opcode{code}"),
         }
     }
 }
 
 /// A DNS response code.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
 pub enum ResponseCode {
     /// No error condition.
     Success,
@@ -1507,6 +2838,11 @@ pub enum ResponseCode {
     NotImplemented,
     /// The name server refuses to perform the specified operation for policy reasons.
     Refused,
+    /// A name exists when it should not, e.g., because the name that a DNAME
+    /// record substitutes would be too long.
+    NameExists,
+    /// A response code that is not otherwise supported, e.g., NOTAUTH (9).
+    Unknown(u8),
 }
 
 impl From<u8> for ResponseCode {
@@ -1520,7 +2856,8 @@ impl From<u8> for ResponseCode {
             3 => NameError,
             4 => NotImplemented,
             5 => Refused,
-            _ => panic!("unsupported response code: {value}"),
+            6 => NameExists,
+            _ => Unknown(value),
         }
     }
 }
@@ -1536,6 +2873,8 @@ impl From<ResponseCode> for u8 {
             NameError => 3,
             NotImplemented => 4,
             Refused => 5,
+            NameExists => 6,
+            Unknown(code) => code & 0b1111,
         }
     }
 }
@@ -1545,12 +2884,14 @@ impl Display for ResponseCode {
         use ResponseCode::*;
 
         let str = match self {
+            Unknown(code) => return write!(f, "rcode{code}"),
             Success => "success",
             FormatError => "format_error",
             ServerFailure => "server_failure",
             NameError => "nonexistent_domain",
             NotImplemented => "not_implemented",
             Refused => "refused",
+            NameExists => "name_exists",
         };
 
         write!(f, "{str}")
@@ -1595,19 +2936,19 @@ impl Default for Header {
 
 impl Header {
     /// Creates a Header from a byte stream.
-    fn from_bytes(bytes: &mut Bytes) -> Self {
-        let id = bytes.read_u16().unwrap();
+    pub fn from_bytes(bytes: &mut Bytes) -> Result<Self, DecodeError> {
+        let id = bytes.read_u16()?;
 
         let (is_response, op_code, is_authority, is_truncated, recursion_desired) = {
-            let byte = bytes.read().unwrap();
+            let byte = bytes.read()?;
             let is_response = ((byte >> 7) & 1) == 1;
-            let op_code = (byte & (0b1111 << 3)) >> 3;
+            let op_code = OperationCode::from((byte & (0b1111 << 3)) >> 3);
             let is_authority = ((byte >> 2) & 1) == 1;
             let is_truncated = ((byte >> 1) & 1) == 1;
             let recursion_desired = (byte & 1) == 1;
             (
                 is_response,
-                op_code.into(),
+                op_code,
                 is_authority,
                 is_truncated,
                 recursion_desired,
@@ -1615,18 +2956,18 @@ impl Header {
         };
 
         let (recursion_available, resp_code) = {
-            let byte = bytes.read().unwrap();
+            let byte = bytes.read()?;
             let recursion_available = ((byte >> 7) & 1) == 1;
-            let resp_code = byte & 0b1111;
-            (recursion_available, resp_code.into())
+            let resp_code = ResponseCode::from(byte & 0b1111);
+            (recursion_available, resp_code)
         };
 
-        let question_count = bytes.read_u16().unwrap();
-        let answer_count = bytes.read_u16().unwrap();
-        let authority_count = bytes.read_u16().unwrap();
-        let additional_count = bytes.read_u16().unwrap();
+        let question_count = bytes.read_u16()?;
+        let answer_count = bytes.read_u16()?;
+        let authority_count = bytes.read_u16()?;
+        let additional_count = bytes.read_u16()?;
 
-        Self {
+        Ok(Self {
             id,
             is_response,
             op_code,
@@ -1639,7 +2980,7 @@ impl Header {
             answer_count,
             authority_count,
             additional_count,
-        }
+        })
     }
 
     /// Converts a Header to a byte stream.
@@ -1647,12 +2988,12 @@ impl Header {
         bytes.write_u16(self.id);
 
         let codes1 = {
-            let mut byte = 0000_0000;
+            let mut byte = 0b0000_0000;
             byte |= (self.is_response as u8) << 7;
             byte |= u8::from(self.op_code.clone()) << 3;
             byte |= (self.is_authority as u8) << 2;
             byte |= (self.is_truncated as u8) << 1;
-            byte |= (self.recursion_desired as u8) << 0;
+            byte |= self.recursion_desired as u8;
             byte
         };
         bytes.write(codes1);
@@ -1690,7 +3031,7 @@ impl Display for Header {
 }
 
 /// The type of a DNS question.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum QuestionType {
     /// A host address.
     A,
@@ -1726,6 +3067,16 @@ pub enum QuestionType {
     TXT,
     /// An IPv6 host address.
     AAAA,
+    /// A service location.
+    SRV,
+    /// A delegation of a subtree of the namespace.
+    DNAME,
+    /// A certification authority authorization.
+    CAA,
+    /// A general service binding.
+    SVCB,
+    /// A service binding for HTTPS.
+    HTTPS,
     /// A request for a transfer of an entire zone.
     AXFR,
     /// A request for mailbox-related records (MB, MG or MR).
@@ -1734,6 +3085,8 @@ pub enum QuestionType {
     MAILA,
     /// A request for all records
     ALL,
+    /// A type without dedicated support, identified by its code.
+    Unknown(u16),
 }
 
 impl QuestionType {
@@ -1765,11 +3118,16 @@ impl From<u16> for QuestionType {
             15 => MX,
             16 => TXT,
             28 => AAAA,
+            33 => SRV,
+            39 => DNAME,
+            64 => SVCB,
+            65 => HTTPS,
+            257 => CAA,
             252 => AXFR,
             253 => MAILB,
             254 => MAILA,
             255 => ALL,
-            _ => panic!("unsupported question type: {value}"),
+            _ => Unknown(value),
         }
     }
 }
@@ -1796,10 +3154,16 @@ impl From<QuestionType> for u16 {
             MX => 15,
             TXT => 16,
             AAAA => 28,
+            SRV => 33,
+            DNAME => 39,
+            SVCB => 64,
+            HTTPS => 65,
+            CAA => 257,
             AXFR => 252,
             MAILB => 253,
             MAILA => 254,
             ALL => 255,
+            Unknown(code) => code,
         }
     }
 }
@@ -1828,11 +3192,19 @@ impl FromStr for QuestionType {
             "MX" => MX,
             "TXT" => TXT,
             "AAAA" => AAAA,
+            "SRV" => SRV,
+            "DNAME" => DNAME,
+            "CAA" => CAA,
+            "SVCB" => SVCB,
+            "HTTPS" => HTTPS,
             "AXFR" => AXFR,
             "MAILB" => MAILB,
             "MAILA" => MAILA,
             "ALL" => ALL,
-            _ => bail!("unsupported qtype: {s}"),
+            _ => match s.strip_prefix("TYPE").map(|c| c.parse::<u16>()) {
+                Some(Ok(code)) => QuestionType::from(code),
+                _ => bail!("unsupported qtype: {s}"),
+            },
         };
 
         Ok(qtype)
@@ -1861,18 +3233,33 @@ impl Display for QuestionType {
             MX => "MX",
             TXT => "TXT",
             AAAA => "AAAA",
+            SRV => "SRV",
+            DNAME => "DNAME",
+            CAA => "CAA",
+            SVCB => "SVCB",
+            HTTPS => "HTTPS",
             AXFR => "AXFR",
             MAILB => "MAILB",
             MAILA => "MAILA",
             ALL => "ALL",
+            Unknown(code) => return write!(f, "TYPE{code}"),
         };
 
         write!(f, "{text}")
     }
 }
 
+impl Serialize for QuestionType {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
 /// The class of a DNS question.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
 pub enum QuestionClass {
     /// Internet.
     In,
@@ -1886,17 +3273,20 @@ pub enum QuestionClass {
     Any,
 }
 
-impl From<u16> for QuestionClass {
-    fn from(value: u16) -> Self {
+impl TryFrom<u16> for QuestionClass {
+    type Error = u16;
+
+    /// Returns the unrecognized value as the error.
+    fn try_from(value: u16) -> std::result::Result<Self, Self::Error> {
         use QuestionClass::*;
 
         match value {
-            1 => In,
-            2 => Cs,
-            3 => Ch,
-            4 => Hs,
-            255 => Any,
-            _ => panic!("unsupported question class: {value}"),
+            1 => Ok(In),
+            2 => Ok(Cs),
+            3 => Ok(Ch),
+            4 => Ok(Hs),
+            255 => Ok(Any),
+            _ => Err(value),
         }
     }
 }
@@ -1947,7 +3337,7 @@ impl Display for QuestionClass {
 }
 
 /// A DNS question.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
 pub struct Question {
     pub name: Name,
     pub q_type: QuestionType,
@@ -1956,16 +3346,20 @@ pub struct Question {
 
 impl Question {
     /// Creates a Question from a byte stream.
-    fn from_bytes(bytes: &mut Bytes) -> Self {
-        let name = Name::from_bytes(bytes);
-        let q_type = bytes.read_u16().unwrap().into();
-        let q_class = bytes.read_u16().unwrap().into();
+    fn from_bytes(bytes: &mut Bytes) -> Result<Self, DecodeError> {
+        let name = Name::from_bytes(bytes)?;
 
-        Self {
+        let q_type = bytes.read_u16()?.into();
+
+        let offset = bytes.pos();
+        let q_class = QuestionClass::try_from(bytes.read_u16()?)
+            .map_err(|class| DecodeError::UnknownClass { offset, class })?;
+
+        Ok(Self {
             name,
             q_type,
             q_class,
-        }
+        })
     }
 
     /// Converts a Question to a byte stream.
@@ -1977,12 +3371,16 @@ impl Question {
 }
 
 impl Display for Question {
+    /// The alternate form (`{:#}`) shows the name with Unicode labels (see
+    /// [`Name`]).
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {} {}", self.name, self.q_class, self.q_type)
+        self.name.fmt(f)?;
+        write!(f, " {} {}", self.q_class, self.q_type)
     }
 }
 
 /// A byte stream.
+#[derive(Default)]
 pub struct Bytes {
     buf: Vec<u8>,
     pos: usize,
@@ -1995,11 +3393,7 @@ pub struct Bytes {
 impl Bytes {
     /// Creates a new Bytes iterator with an empty buffer.
     pub fn new() -> Self {
-        Self {
-            buf: vec![],
-            pos: 0,
-            occs: HashMap::new(),
-        }
+        Default::default()
     }
 
     /// Creates a new Bytes iterator from a buffer.
@@ -2033,55 +3427,62 @@ impl Bytes {
 
     /// Reads the next byte from the buffer.
     ///
-    /// Returns None if the end of the buffer has been reached.
-    fn read(&mut self) -> Option<u8> {
-        if self.remainder().len() == 0 {
-            return None;
-        }
-        let byte = self.remainder()[0];
+    /// Returns an error if the end of the buffer has been reached.
+    fn read(&mut self) -> Result<u8, DecodeError> {
+        let byte = self.peek()?;
         self.pos += 1;
-        Some(byte)
+        Ok(byte)
     }
 
     /// Reads the next byte from the buffer without advancing the position.
     ///
-    /// Returns None if the end of the buffer has been reached.
-    fn peek(&mut self) -> Option<u8> {
-        if self.remainder().len() == 0 {
-            return None;
+    /// Returns an error if the end of the buffer has been reached.
+    fn peek(&mut self) -> Result<u8, DecodeError> {
+        match self.remainder().first() {
+            Some(byte) => Ok(*byte),
+            None => Err(DecodeError::Truncated { offset: self.pos }),
         }
-        let byte = self.remainder()[0];
-        Some(byte)
     }
 
     /// Reads the next n bytes from the buffer.
     ///
-    /// Returns None if the end of the buffer has been reached.
-    fn read_exact(&mut self, n: usize) -> Option<Vec<u8>> {
+    /// Returns an error if the end of the buffer has been reached.
+    fn read_exact(&mut self, n: usize) -> Result<Vec<u8>, DecodeError> {
         if self.remainder().len() < n {
-            return None;
+            return Err(DecodeError::Truncated { offset: self.pos });
         }
-        let bytes: Vec<_> = self.remainder()[..n].iter().map(|b| b.to_owned()).collect();
+        let bytes = self.remainder()[..n].to_vec();
         self.pos += n;
-        Some(bytes)
+        Ok(bytes)
     }
 
     /// Reads a u16 from the buffer.
     ///
-    /// Returns None if the end of the buffer has been reached.
-    fn read_u16(&mut self) -> Option<u16> {
+    /// Returns an error if the end of the buffer has been reached.
+    fn read_u16(&mut self) -> Result<u16, DecodeError> {
         self.read_exact(2)
             .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
     }
 
     /// Reads a u32 from the buffer.
     ///
-    /// Returns None if the end of the buffer has been reached.
-    fn read_u32(&mut self) -> Option<u32> {
+    /// Returns an error if the end of the buffer has been reached.
+    fn read_u32(&mut self) -> Result<u32, DecodeError> {
         self.read_exact(4)
             .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
     }
 
+    /// Reads a length-prefixed character string from the buffer.
+    ///
+    /// Returns an error if the end of the buffer has been reached or the
+    /// string is not valid UTF-8.
+    fn read_text(&mut self) -> Result<String, DecodeError> {
+        let offset = self.pos;
+        let len = self.read()?;
+        let bytes = self.read_exact(len as usize)?;
+        String::from_utf8(bytes).map_err(|_| DecodeError::BadText { offset })
+    }
+
     /// Writes a byte to the buffer.
     fn write(&mut self, byte: u8) {
         self.buf.push(byte);
@@ -2137,11 +3538,101 @@ impl Bytes {
     }
 }
 
+/// An error encountered while decoding a message from a byte stream.
+///
+/// Each variant records the offset in the stream at which the problem was
+/// detected.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DecodeError {
+    /// The stream ended before the item being read was complete.
+    Truncated { offset: usize },
+    /// A label had an invalid length or contents.
+    BadLabel { offset: usize },
+    /// A name was longer than 255 bytes.
+    NameTooLong { offset: usize },
+    /// A compression pointer did not point to an earlier position.
+    PointerLoop { offset: usize },
+    /// A character string was not valid UTF-8.
+    BadText { offset: usize },
+    /// A service binding parameter had an invalid value.
+    BadSvcParam { offset: usize },
+    /// The class of a record or question is not supported.
+    UnknownClass { offset: usize, class: u16 },
+    /// The record data did not match the length declared for it.
+    RdataLengthMismatch {
+        offset: usize,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use DecodeError::*;
+
+        match self {
+            Truncated { offset } => write!(f, "unexpected end of message at offset {offset}"),
+            BadLabel { offset } => write!(f, "malformed label at offset {offset}"),
+            NameTooLong { offset } => write!(f, "name at offset {offset} exceeds 255 bytes"),
+            PointerLoop { offset } => write!(f, "compression pointer loop at offset {offset}"),
+            BadText { offset } => write!(f, "invalid character string at offset {offset}"),
+            BadSvcParam { offset } => write!(f, "invalid service parameter at offset {offset}"),
+            UnknownClass { offset, class } => {
+                write!(f, "unsupported class {class} at offset {offset}")
+            }
+            RdataLengthMismatch {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "record data at offset {offset} has length {actual}, expected {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// An error encountered while parsing a name from its presentation format.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ParseNameError {
+    /// A label other than the root label was empty.
+    EmptyLabel,
+    /// A label was longer than 63 bytes.
+    LabelTooLong,
+    /// A name was longer than 255 bytes.
+    NameTooLong,
+    /// An escape sequence was incomplete or out of range.
+    BadEscape,
+    /// An internationalized name could not be converted to A-labels.
+    BadIdn,
+}
+
+impl Display for ParseNameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ParseNameError::*;
+
+        match self {
+            EmptyLabel => write!(f, "name contains an empty label"),
+            LabelTooLong => write!(f, "label exceeds 63 bytes"),
+            NameTooLong => write!(f, "name exceeds 255 bytes"),
+            BadEscape => write!(f, "invalid escape sequence"),
+            BadIdn => write!(f, "invalid internationalized domain name"),
+        }
+    }
+}
+
+impl std::error::Error for ParseNameError {}
+
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use std::{collections::HashSet, str::FromStr};
 
-    use crate::{Name, Zone};
+    use crate::{
+        Bytes, Class, DecodeError, Lookup, Message, Name, OperationCode, ParseNameError,
+        QuestionType, Record, ResponseCode, Zone,
+    };
 
     #[test]
     fn parse_toml() {
@@ -2157,10 +3648,8 @@ mod tests {
         "#;
 
         let zone: Zone = Zone::from_toml(input).unwrap();
-        assert_eq!(
-            zone.records[0].name(),
-            &Name::from_str("example.com.").unwrap()
-        )
+        let records: Vec<_> = zone.records().collect();
+        assert_eq!(records[0].name(), &Name::from_str("example.com.").unwrap())
     }
 
     #[test]
@@ -2175,10 +3664,379 @@ mod tests {
         );
     }
 
+    #[test]
+    fn names_ignore_case() {
+        let name = Name::from_str("Example.COM.").unwrap();
+        assert_eq!(name, Name::from_str("example.com.").unwrap());
+        assert_eq!(name.to_string(), "Example.COM.");
+
+        let names: HashSet<_> = ["example.com.", "EXAMPLE.com.", "www.example.com."]
+            .iter()
+            .map(|s| Name::from_str(s).unwrap())
+            .collect();
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    fn names_sort_canonically() {
+        // the example from RFC 4034 section 6.1, without escaped labels
+        let expected = [
+            "example.",
+            "a.example.",
+            "yljkjljk.a.example.",
+            "Z.a.example.",
+            "zABC.a.EXAMPLE.",
+            "z.example.",
+            "*.z.example.",
+        ];
+        let mut names: Vec<_> = expected
+            .iter()
+            .rev()
+            .map(|s| Name::from_str(s).unwrap())
+            .collect();
+        names.sort();
+        let names: Vec<_> = names.iter().map(|n| n.to_string()).collect();
+        assert_eq!(names, expected);
+    }
+
+    #[test]
+    fn names_allow_any_label_bytes() {
+        for text in [
+            "_dmarc.example.com.",
+            "_443._tcp.example.com.",
+            "a\\.b\\000.example.",
+        ] {
+            assert_eq!(Name::from_str(text).unwrap().to_string(), text);
+        }
+
+        let name = Name::from_str("a\\.b.example.").unwrap();
+        let mut bytes = Bytes::new();
+        name.to_bytes(&mut bytes);
+        assert_eq!(&bytes.used()[..4], b"\x03a.b");
+
+        let buf = [2, 0xff, b'x', 0];
+        let name = Name::from_bytes(&mut Bytes::from_buf(&buf)).unwrap();
+        assert_eq!(name.to_string(), "\\255x.");
+        assert!(!name.is_hostname());
+        assert!(Name::from_str("host-1.example.com.").unwrap().is_hostname());
+    }
+
+    #[test]
+    fn names_convert_unicode_labels() {
+        let name = Name::from_str("Bücher.example").unwrap();
+        assert_eq!(name.to_string(), "xn--bcher-kva.example.");
+        assert_eq!(format!("{name:#}"), "bücher.example.");
+        assert_eq!(name, Name::from_str("xn--bcher-kva.example.").unwrap());
+
+        let name = Name::from_str("💩.la.").unwrap();
+        assert_eq!(name.to_string(), "xn--ls8h.la.");
+
+        // invalid A-labels are shown as they are
+        let name = Name::from_str("xn--a.example.").unwrap();
+        assert_eq!(format!("{name:#}"), "xn--a.example.");
+
+        assert_eq!(
+            Name::from_str("\u{301}a.example."),
+            Err(ParseNameError::BadIdn)
+        );
+    }
+
+    #[test]
+    fn names_report_errors() {
+        let label = "a".repeat(63);
+        assert!(Name::from_str(&format!("{label}.")).is_ok());
+        assert_eq!(
+            Name::from_str(&format!("a{label}.")),
+            Err(ParseNameError::LabelTooLong)
+        );
+        assert_eq!(
+            Name::from_str(&[label.as_str(); 4].join(".")),
+            Err(ParseNameError::NameTooLong)
+        );
+        assert_eq!(Name::from_str("a..b."), Err(ParseNameError::EmptyLabel));
+        assert_eq!(Name::from_str("a\\25."), Err(ParseNameError::BadEscape));
+        assert_eq!(Name::from_str("a\\256."), Err(ParseNameError::BadEscape));
+    }
+
     #[test]
     fn name_to_wildcard() {
         let name = Name::from_str("example.com.").unwrap();
         let wildcard = name.to_wildcard();
         assert_eq!(&wildcard.to_string(), "*.com.")
     }
+
+    #[test]
+    fn decode_truncated_message() {
+        let mut bytes = Bytes::from_buf(&[0, 1, 0, 0, 0, 1]);
+        let err = Message::from_bytes(&mut bytes).unwrap_err();
+        assert_eq!(err, DecodeError::Truncated { offset: 6 });
+    }
+
+    #[test]
+    fn decode_unknown_codes() {
+        // a response with opcode 5 (UPDATE) and rcode 9 (NOTAUTH)
+        let buf = [0, 1, 0b1010_1000, 9, 0, 0, 0, 0, 0, 0, 0, 0];
+        let message = Message::from_bytes(&mut Bytes::from_buf(&buf)).unwrap();
+        assert_eq!(message.header.op_code, OperationCode::Unknown(5));
+        assert_eq!(message.header.resp_code, ResponseCode::Unknown(9));
+        assert_eq!(message.header.resp_code.to_string(), "rcode9");
+
+        let mut bytes = Bytes::new();
+        message.to_bytes(&mut bytes);
+        assert_eq!(bytes.used(), buf);
+    }
+
+    #[test]
+    fn decode_pointer_loop() {
+        let mut buf = vec![0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        // question name that points to itself
+        buf.extend([0b1100_0000, 12, 0, 1, 0, 1]);
+        let mut bytes = Bytes::from_buf(&buf);
+        let err = Message::from_bytes(&mut bytes).unwrap_err();
+        assert_eq!(err, DecodeError::PointerLoop { offset: 12 });
+    }
+
+    #[test]
+    fn decode_rdata_length_mismatch() {
+        let mut buf = vec![0, 1, 0x80, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        // root A record that declares 5 bytes of data
+        buf.extend([0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 5, 127, 0, 0, 1, 0]);
+        let mut bytes = Bytes::from_buf(&buf);
+        let err = Message::from_bytes(&mut bytes).unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::RdataLengthMismatch {
+                offset: 23,
+                expected: 5,
+                actual: 4
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_record_round_trip() {
+        let record = Record::Unknown {
+            name: Name::from_str("example.com.").unwrap(),
+            class: Class::In,
+            ttl: 60,
+            r_type: 1234,
+            data: vec![0xde, 0xad],
+        };
+        assert_eq!(record.to_string(), "example.com. IN 60 TYPE1234 \\# 2 dead");
+
+        let mut bytes = Bytes::new();
+        record.to_bytes(&mut bytes);
+        let mut bytes = Bytes::from_buf(bytes.used());
+        assert_eq!(Record::from_bytes(&mut bytes).unwrap(), record);
+    }
+
+    #[test]
+    fn parse_toml_generic_records() {
+        let input = r#"
+            name = "example.com."
+
+            [[records]]
+            name = "example.com."
+            class = "IN"
+            ttl = 60
+            type = "TYPE1234"
+            data = '\# 2 dead'
+
+            [[records]]
+            name = "example.com."
+            class = "IN"
+            ttl = 60
+            type = "TYPE1"
+            data = '\# 4 0a000001'
+        "#;
+
+        let zone = Zone::from_toml(input).unwrap();
+        let records: Vec<_> = zone.records().collect();
+        assert!(matches!(records[0], Record::Unknown { r_type: 1234, .. }));
+        assert_eq!(records[1].to_string(), "example.com. IN 60 A 10.0.0.1");
+    }
+
+    #[test]
+    fn parse_toml_caa_tags() {
+        let zone = |tag: &str| {
+            let input = format!(
+                r#"
+                name = "example.com."
+
+                [[records]]
+                name = "example.com."
+                class = "IN"
+                ttl = 60
+                type = "CAA"
+                flags = 0
+                tag = "{tag}"
+                value = "letsencrypt.org"
+                "#
+            );
+            Zone::from_toml(&input)
+        };
+
+        let valid = zone("issue").unwrap();
+        assert_eq!(
+            valid.records().next().unwrap().to_string(),
+            "example.com. IN 60 CAA 0 issue \"letsencrypt.org\""
+        );
+        assert!(zone("contactemail").is_ok());
+        assert!(zone("issue-wild").is_err());
+    }
+
+    #[test]
+    fn txt_preserves_string_boundaries() {
+        let input = format!(
+            r#"
+            name = "example.com."
+
+            [[records]]
+            name = "example.com."
+            class = "IN"
+            ttl = 60
+            type = "TXT"
+            content = "{}"
+
+            [[records]]
+            name = "example.com."
+            class = "IN"
+            ttl = 60
+            type = "TXT"
+            content = ["v=spf1", "say \"hi\""]
+            "#,
+            "a".repeat(300)
+        );
+
+        let zone = Zone::from_toml(&input).unwrap();
+        let records: Vec<_> = zone.records().collect();
+        let Record::Txt { content, .. } = records[0] else {
+            panic!("expected TXT record");
+        };
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0].as_bytes().len(), 255);
+        assert_eq!(
+            records[1].to_string(),
+            r#"example.com. IN 60 TXT "v=spf1" "say \"hi\"""#
+        );
+
+        let mut bytes = Bytes::new();
+        records[1].to_bytes(&mut bytes);
+        let mut bytes = Bytes::from_buf(bytes.used());
+        assert_eq!(&Record::from_bytes(&mut bytes).unwrap(), records[1]);
+    }
+
+    #[test]
+    fn zone_rejects_records_outside_apex() {
+        let origin = Name::from_str("example.com.").unwrap();
+        assert!(Zone::from_master("www 60 IN A 192.0.2.1", Some(origin.clone())).is_ok());
+
+        let result = Zone::from_master("www.example.net. 60 IN A 192.0.2.1", Some(origin));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn zone_from_file_with_origin() {
+        let path = std::env::temp_dir().join(format!("dex-{}.zone", std::process::id()));
+        std::fs::write(
+            &path,
+            "@ 60 IN SOA ns admin 1 7200 3600 1209600 300\nns 60 IN A 192.0.2.1\n",
+        )
+        .unwrap();
+
+        let origin = Name::from_str("example.com.").unwrap();
+        let zone = Zone::from_file(&path, Some(origin.clone()));
+        let without_origin = Zone::from_file(&path, None);
+        std::fs::remove_file(&path).unwrap();
+
+        let zone = zone.unwrap();
+        assert_eq!(zone.name(), &origin);
+        assert!(zone.contains_name(&Name::from_str("ns.example.com.").unwrap()));
+        assert!(without_origin.is_err());
+    }
+
+    #[test]
+    fn zone_index_queries() {
+        let zone = Zone::from_master(
+            "$ORIGIN example.com.\n\
+             @ 60 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             WWW 60 IN A 192.0.2.1\n\
+             *.sub 60 IN A 192.0.2.2\n\
+             host.deep.sub 60 IN A 192.0.2.3\n",
+            None,
+        )
+        .unwrap();
+        let name = |s: &str| Name::from_str(s).unwrap();
+
+        assert_eq!(zone.find_with_name(&name("www.example.com.")).len(), 1);
+        assert!(zone.contains_name(&name("deep.sub.example.com.")));
+        assert!(!zone.contains_name(&name("other.example.com.")));
+
+        assert_eq!(
+            zone.closest_encloser(&name("a.b.deep.sub.example.com.")),
+            Some(name("deep.sub.example.com."))
+        );
+        assert_eq!(zone.closest_encloser(&name("example.net.")), None);
+        assert_eq!(zone.find_wildcard(&name("a.b.sub.example.com.")).len(), 1);
+        assert!(zone
+            .find_wildcard(&name("a.deep.sub.example.com."))
+            .is_empty());
+
+        // names in canonical order: the apex, *.sub, host.deep.sub and www
+        assert_eq!(
+            zone.predecessor(&name("host.deep.sub.example.com.")),
+            Some(&name("*.sub.example.com."))
+        );
+        assert_eq!(
+            zone.successor(&name("host.deep.sub.example.com.")),
+            Some(&name("www.example.com."))
+        );
+        assert_eq!(
+            zone.predecessor(&name("a.sub.example.com.")),
+            Some(&name("*.sub.example.com."))
+        );
+        assert_eq!(
+            zone.successor(&name("a.sub.example.com.")),
+            Some(&name("host.deep.sub.example.com."))
+        );
+        assert_eq!(zone.predecessor(&name("example.com.")), None);
+        assert_eq!(
+            zone.successor(&name("example.com.")),
+            Some(&name("*.sub.example.com."))
+        );
+        assert_eq!(
+            zone.predecessor(&name("WWW.example.com.")),
+            Some(&name("host.deep.sub.example.com."))
+        );
+        assert_eq!(zone.successor(&name("WWW.example.com.")), None);
+
+        assert!(matches!(
+            zone.lookup(&name("a.b.sub.example.com."), &QuestionType::A),
+            Lookup::Answer(records) if records[0].name() == &name("a.b.sub.example.com.")
+        ));
+        assert_eq!(
+            zone.lookup(&name("a.deep.sub.example.com."), &QuestionType::A),
+            Lookup::NameError
+        );
+        assert_eq!(
+            zone.lookup(&name("deep.sub.example.com."), &QuestionType::A),
+            Lookup::NoData
+        );
+    }
+
+    #[test]
+    fn txt_decodes_binary_data() {
+        let buf = [0, 0, 16, 0, 1, 0, 0, 0, 60, 0, 3, 2, 0xff, b'"'];
+        let mut bytes = Bytes::from_buf(&buf);
+        let record = Record::from_bytes(&mut bytes).unwrap();
+        assert_eq!(record.to_string(), r#". IN 60 TXT "\255\"""#);
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            r#"{"type":"TXT","name":".","class":"IN","ttl":60,"content":["\\255\\\""]}"#
+        );
+
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(serde_json::from_str::<Record>(&json).unwrap(), record);
+        assert!(serde_json::from_str::<Record>(&json.replace("255", "256")).is_err());
+    }
 }
@@ -3,7 +3,9 @@ use env_logger::fmt::Formatter;
 use std::io::Write;
 
 /// Initialize the logger.
-pub fn init_logger() {
+///
+/// Messages above the given level are not printed.
+pub fn init_logger(level: log::LevelFilter) {
     let format = |buf: &mut Formatter, record: &log::Record| {
         use log::Level::*;
         let level = {
@@ -28,6 +30,6 @@ pub fn init_logger() {
 
     env_logger::builder()
         .format(format)
-        .filter_level(log::LevelFilter::Warn)
+        .filter_level(level)
         .init();
 }
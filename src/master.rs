@@ -0,0 +1,898 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    slice::Iter,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::{
+    generic_data, split_unescaped, svcb::SvcParam, CharacterString, Class, Name, QuestionType,
+    Record, RecordData, Zone,
+};
+
+/// Maximum nesting depth of `$INCLUDE` directives.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Maximum number of records that the `$GENERATE` directives of a zone may
+/// generate, including those in included files.
+const MAX_GENERATE_RECORDS: u32 = 1_000_000;
+
+/// A token in a master file.
+struct Token {
+    /// Text of the token, with escape sequences left in place.
+    text: String,
+    /// Whether the token was written as a quoted string.
+    quoted: bool,
+}
+
+/// A logical line of a master file.
+///
+/// An entry spans several physical lines when parentheses are used.
+struct Entry {
+    /// Physical line on which the entry starts.
+    line: usize,
+    /// Whether the entry starts with whitespace, in which case the owner of
+    /// the record is omitted.
+    blank_owner: bool,
+    /// Tokens in the entry.
+    tokens: Vec<Token>,
+}
+
+/// Splits a master file into entries.
+///
+/// Comments are removed, parenthesized groups are joined into a single entry
+/// and quoted strings are kept as single tokens.
+fn tokenize(input: &str) -> Result<Vec<Entry>> {
+    /// Pushes the current token, if any, onto a list of tokens.
+    fn flush(current: &mut String, tokens: &mut Vec<Token>) {
+        if !current.is_empty() {
+            let text = std::mem::take(current);
+            tokens.push(Token {
+                text,
+                quoted: false,
+            });
+        }
+    }
+
+    let mut entries = vec![];
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut blank_owner = false;
+    let mut at_line_start = true;
+    let mut start_line = 1;
+    let mut line = 1;
+    let mut depth = 0;
+
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if at_line_start {
+            blank_owner = c == ' ' || c == '\t';
+            start_line = line;
+            at_line_start = false;
+        }
+
+        match c {
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .with_context(|| format!("line {line}: incomplete escape sequence"))?;
+                if escaped == '\n' {
+                    line += 1;
+                }
+                current.push(c);
+                current.push(escaped);
+            }
+            '"' => {
+                flush(&mut current, &mut tokens);
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            let escaped = chars.next().with_context(|| {
+                                format!("line {line}: incomplete escape sequence")
+                            })?;
+                            text.push('\\');
+                            text.push(escaped);
+                        }
+                        Some('\n') | None => bail!("line {line}: unterminated quoted string"),
+                        Some(c) => text.push(c),
+                    }
+                }
+                tokens.push(Token { text, quoted: true });
+            }
+            ';' => {
+                flush(&mut current, &mut tokens);
+                // the newline itself still ends the entry
+                while chars.clone().next().is_some_and(|c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '(' => {
+                flush(&mut current, &mut tokens);
+                depth += 1;
+            }
+            ')' => {
+                flush(&mut current, &mut tokens);
+                if depth == 0 {
+                    bail!("line {line}: unbalanced parentheses");
+                }
+                depth -= 1;
+            }
+            '\n' => {
+                flush(&mut current, &mut tokens);
+                line += 1;
+                if depth == 0 {
+                    if !tokens.is_empty() {
+                        entries.push(Entry {
+                            line: start_line,
+                            blank_owner,
+                            tokens: std::mem::take(&mut tokens),
+                        });
+                    }
+                    at_line_start = true;
+                }
+            }
+            c if c.is_whitespace() => flush(&mut current, &mut tokens),
+            c => current.push(c),
+        }
+    }
+
+    flush(&mut current, &mut tokens);
+    if depth != 0 {
+        bail!("line {start_line}: unbalanced parentheses");
+    }
+    if !tokens.is_empty() {
+        entries.push(Entry {
+            line: start_line,
+            blank_owner,
+            tokens,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Resolves the escape sequences in a text (see [`crate::unescape`]).
+fn unescape(text: &str) -> Result<Vec<u8>> {
+    crate::unescape(text).with_context(|| format!("invalid escape sequence in {text}"))
+}
+
+/// Parses a TTL.
+///
+/// The TTL may be given as a number of seconds or as a sequence of numbers
+/// with units (`s`, `m`, `h`, `d` or `w`), e.g., `1h30m`.
+fn parse_ttl(text: &str) -> Option<u32> {
+    if let Ok(ttl) = text.parse() {
+        return Some(ttl);
+    }
+
+    let mut total: u32 = 0;
+    let mut value: Option<u32> = None;
+    for c in text.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            value = Some(value.unwrap_or(0).checked_mul(10)?.checked_add(digit)?);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        total = total.checked_add(value.take()?.checked_mul(unit)?)?;
+    }
+    total.checked_add(value.unwrap_or(0))
+}
+
+/// Parses a class mnemonic.
+fn parse_class(text: &str) -> Option<Class> {
+    let class = match text.to_ascii_uppercase().as_str() {
+        "IN" => Class::In,
+        "CS" => Class::Cs,
+        "CH" => Class::Ch,
+        "HS" => Class::Hs,
+        text => {
+            let code = text.strip_prefix("CLASS")?.parse::<u16>().ok()?;
+            Class::try_from(code).ok()?
+        }
+    };
+    Some(class)
+}
+
+/// Returns the next token of record data.
+fn next_field<'a>(fields: &mut Iter<'a, Token>) -> Result<&'a Token> {
+    fields.next().context("missing record data")
+}
+
+/// Parses the next token of record data.
+fn parse_field<T: FromStr>(fields: &mut Iter<'_, Token>, what: &str) -> Result<T> {
+    let field = next_field(fields)?;
+    field
+        .text
+        .parse()
+        .map_err(|_| anyhow!("invalid {what}: {}", field.text))
+}
+
+/// Parses the next token of record data as a character string.
+fn character_string(fields: &mut Iter<'_, Token>) -> Result<Vec<u8>> {
+    let field = next_field(fields)?;
+    let bytes = unescape(&field.text)?;
+    if bytes.len() > 255 {
+        bail!("character string is longer than 255 bytes: {}", field.text);
+    }
+    Ok(bytes)
+}
+
+/// Replaces the iterator in a `$GENERATE` template with a value.
+///
+/// `$` is replaced with the value, and `${offset,width,base}` with the value
+/// plus an offset, padded to a width and formatted in a base (`d`, `o`, `x`
+/// or `X`). `\$` produces a literal `$`.
+fn substitute(template: &str, value: u32) -> Result<String> {
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('$') => text.push('$'),
+                Some(c) => {
+                    text.push('\\');
+                    text.push(c);
+                }
+                None => text.push('\\'),
+            },
+            '$' => {
+                let mut modifiers = String::new();
+                if chars.next_if_eq(&'{').is_some() {
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => modifiers.push(c),
+                            None => bail!("unterminated modifier in {template}"),
+                        }
+                    }
+                }
+
+                let mut modifiers = modifiers.split(',');
+                let invalid = || anyhow!("invalid modifier in {template}");
+                let offset: i64 = match modifiers.next() {
+                    Some("") | None => 0,
+                    Some(offset) => offset.parse().map_err(|_| invalid())?,
+                };
+                let width: usize = match modifiers.next() {
+                    Some(width) => width.parse().map_err(|_| invalid())?,
+                    None => 0,
+                };
+                let value = u64::try_from(i64::from(value) + offset).map_err(|_| invalid())?;
+                let value = match modifiers.next().unwrap_or("d") {
+                    "d" => format!("{value:0width$}"),
+                    "o" => format!("{value:0width$o}"),
+                    "x" => format!("{value:0width$x}"),
+                    "X" => format!("{value:0width$X}"),
+                    _ => return Err(invalid()),
+                };
+                text.push_str(&value);
+            }
+            c => text.push(c),
+        }
+    }
+    Ok(text)
+}
+
+/// Parser state for a master file.
+struct Parser {
+    /// Origin that relative names are resolved against.
+    origin: Option<Name>,
+    /// TTL set by the `$TTL` directive.
+    default_ttl: Option<u32>,
+    /// Last TTL that was given explicitly.
+    last_ttl: Option<u32>,
+    /// Owner of the last record.
+    last_owner: Option<Name>,
+    /// Class of the last record.
+    last_class: Class,
+    /// Directory that `$INCLUDE` paths are resolved against.
+    dir: PathBuf,
+    /// Nesting depth of `$INCLUDE` directives.
+    depth: usize,
+    /// Number of records generated by `$GENERATE` directives so far.
+    generated: u32,
+    /// Records parsed so far.
+    records: Vec<Record>,
+}
+
+impl Parser {
+    /// Parses the entries of a master file.
+    fn parse(&mut self, input: &str) -> Result<()> {
+        for entry in tokenize(input)? {
+            let line = entry.line;
+            self.parse_entry(entry)
+                .with_context(|| format!("line {line}"))?;
+        }
+        Ok(())
+    }
+
+    /// Parses a directive or a record.
+    fn parse_entry(&mut self, entry: Entry) -> Result<()> {
+        let tokens = &entry.tokens;
+        let directive = &tokens[0];
+        if entry.blank_owner || directive.quoted || !directive.text.starts_with('$') {
+            return self.parse_record(entry.blank_owner, tokens);
+        }
+
+        match directive.text.to_ascii_uppercase().as_str() {
+            "$ORIGIN" => {
+                let [_, origin] = &tokens[..] else {
+                    bail!("$ORIGIN requires a single name");
+                };
+                self.origin = Some(self.parse_name(&origin.text)?);
+            }
+            "$TTL" => {
+                let [_, ttl] = &tokens[..] else {
+                    bail!("$TTL requires a single TTL");
+                };
+                let ttl =
+                    parse_ttl(&ttl.text).with_context(|| format!("invalid TTL: {}", ttl.text))?;
+                self.default_ttl = Some(ttl);
+            }
+            "$INCLUDE" => {
+                let (path, origin) = match &tokens[..] {
+                    [_, path] => (path, None),
+                    [_, path, origin] => (path, Some(self.parse_name(&origin.text)?)),
+                    _ => bail!("$INCLUDE requires a file name and an optional origin"),
+                };
+                if self.depth >= MAX_INCLUDE_DEPTH {
+                    bail!("$INCLUDE is nested too deeply");
+                }
+
+                let path = self.dir.join(String::from_utf8(unescape(&path.text)?)?);
+                let input = fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+
+                // the origin and the current owner revert once the included
+                // file has been read
+                let origin = origin.or_else(|| self.origin.clone());
+                let saved_origin = std::mem::replace(&mut self.origin, origin);
+                let saved_owner = self.last_owner.take();
+                self.depth += 1;
+                let result = self.parse(&input);
+                self.depth -= 1;
+                self.origin = saved_origin;
+                self.last_owner = saved_owner;
+                result.with_context(|| format!("in {}", path.display()))?;
+            }
+            "$GENERATE" => {
+                let [_, range, owner, rest @ ..] = &tokens[..] else {
+                    bail!("$GENERATE requires a range, an owner and record data");
+                };
+
+                let invalid = || anyhow!("invalid range: {}", range.text);
+                let (range, step): (_, u32) = match range.text.split_once('/') {
+                    Some((range, step)) => (range, step.parse().map_err(|_| invalid())?),
+                    None => (range.text.as_str(), 1),
+                };
+                let (start, stop) = range.split_once('-').ok_or_else(invalid)?;
+                let start: u32 = start.parse().map_err(|_| invalid())?;
+                let stop: u32 = stop.parse().map_err(|_| invalid())?;
+                if start > stop || step == 0 {
+                    return Err(invalid());
+                }
+                // the range has one more record than steps, which may not fit
+                // in a u32
+                let steps = (stop - start) / step;
+                if steps >= MAX_GENERATE_RECORDS - self.generated {
+                    bail!(
+                        "$GENERATE range {range} exceeds {MAX_GENERATE_RECORDS} records in total"
+                    );
+                }
+                self.generated += steps + 1;
+
+                for value in (start..=stop).step_by(step as usize) {
+                    let tokens = std::iter::once(owner)
+                        .chain(rest)
+                        .map(|t| {
+                            Ok(Token {
+                                text: substitute(&t.text, value)?,
+                                quoted: t.quoted,
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    self.parse_record(false, &tokens)?;
+                }
+            }
+            directive => bail!("unsupported directive: {directive}"),
+        }
+
+        Ok(())
+    }
+
+    /// Parses a name, resolving it against the origin if it is relative.
+    ///
+    /// `@` refers to the origin itself.
+    fn parse_name(&self, text: &str) -> Result<Name> {
+        let origin = || {
+            self.origin
+                .clone()
+                .with_context(|| format!("no origin to resolve {text} against"))
+        };
+
+        if text == "@" {
+            return origin();
+        }
+        let name = Name::from_str(text).with_context(|| format!("invalid name {text}"))?;
+        let is_absolute = split_unescaped(text, '.').last() == Some(&"");
+        let name = if is_absolute {
+            name
+        } else {
+            name.replace_suffix(&Name::root(), &origin()?)
+                .with_context(|| format!("name is longer than 255 bytes: {text}"))?
+        };
+
+        if name.ancestors().any(|a| a.is_wildcard() && a != name) {
+            bail!("wildcard label must be the first label in {text}");
+        }
+
+        Ok(name)
+    }
+
+    /// Parses a record.
+    ///
+    /// If the owner is blank, the owner of the previous record is used. The
+    /// TTL and class may appear in either order and default to the `$TTL`
+    /// value (or the last explicit TTL) and the previous class respectively.
+    fn parse_record(&mut self, blank_owner: bool, tokens: &[Token]) -> Result<()> {
+        let mut tokens = tokens.iter();
+        let name = if blank_owner {
+            self.last_owner.clone().context("no previous owner")?
+        } else {
+            self.parse_name(&next_field(&mut tokens)?.text)?
+        };
+
+        let mut ttl = None;
+        let mut class = None;
+        let r_type = loop {
+            let token = tokens.next().context("missing record type")?;
+            if ttl.is_none() && token.text.starts_with(|c: char| c.is_ascii_digit()) {
+                let value = parse_ttl(&token.text)
+                    .with_context(|| format!("invalid TTL: {}", token.text))?;
+                ttl = Some(value);
+                continue;
+            }
+            match parse_class(&token.text) {
+                Some(value) if class.is_none() => class = Some(value),
+                _ => break token.text.to_ascii_uppercase(),
+            }
+        };
+
+        let ttl = match ttl {
+            Some(ttl) => {
+                self.last_ttl = Some(ttl);
+                ttl
+            }
+            None => self
+                .default_ttl
+                .or(self.last_ttl)
+                .context("no TTL given and no $TTL default")?,
+        };
+        let class = class.unwrap_or_else(|| self.last_class.clone());
+        self.last_class = class.clone();
+        self.last_owner = Some(name.clone());
+
+        let record = self.parse_rdata(name, class, ttl, &r_type, tokens.as_slice())?;
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// Parses the data of a record.
+    ///
+    /// Data in the generic format of RFC 3597 (`\# <length> <hex>`) is
+    /// accepted for any type.
+    fn parse_rdata(
+        &self,
+        name: Name,
+        class: Class,
+        ttl: u32,
+        r_type: &str,
+        data: &[Token],
+    ) -> Result<Record> {
+        let code = QuestionType::from_str(r_type)
+            .map_err(|_| anyhow!("unsupported record type: {r_type}"))?
+            .code();
+
+        if matches!(data.first(), Some(t) if !t.quoted && t.text == "\\#") {
+            let text = data.iter().map(|t| t.text.as_str()).collect::<Vec<_>>();
+            let text = text.join(" ");
+            let data = generic_data::from_str(&text)
+                .with_context(|| format!("invalid generic record data: {text}"))?;
+            let record = Record::Unknown {
+                name,
+                class,
+                ttl,
+                r_type: code,
+                data,
+            };
+            return record
+                .into_known()
+                .with_context(|| format!("invalid record data for {r_type}"));
+        }
+
+        let mut fields = data.iter();
+        let fields = &mut fields;
+        let record = match code {
+            1 => Record::A {
+                name,
+                class,
+                ttl,
+                addr: parse_field(fields, "IPv4 address")?,
+            },
+            2 => Record::Ns {
+                name,
+                class,
+                ttl,
+                host: self.name_field(fields)?,
+            },
+            3 => Record::Md {
+                name,
+                class,
+                ttl,
+                host: self.name_field(fields)?,
+            },
+            4 => Record::Mf {
+                name,
+                class,
+                ttl,
+                host: self.name_field(fields)?,
+            },
+            5 => Record::Cname {
+                name,
+                class,
+                ttl,
+                host: self.name_field(fields)?,
+            },
+            6 => Record::Soa {
+                name,
+                class,
+                ttl,
+                origin: self.name_field(fields)?,
+                mailbox: self.name_field(fields)?,
+                version: parse_field(fields, "serial")?,
+                refresh: self.ttl_field(fields)?,
+                retry: self.ttl_field(fields)?,
+                expire: self.ttl_field(fields)?,
+                minimum: self.ttl_field(fields)?,
+            },
+            7 => Record::Mb {
+                name,
+                class,
+                ttl,
+                host: self.name_field(fields)?,
+            },
+            8 => Record::Mg {
+                name,
+                class,
+                ttl,
+                host: self.name_field(fields)?,
+            },
+            9 => Record::Mr {
+                name,
+                class,
+                ttl,
+                host: self.name_field(fields)?,
+            },
+            11 => {
+                let addr = parse_field(fields, "IPv4 address")?;
+                let protocol = next_field(fields)?;
+                let protocol = match protocol.text.to_ascii_lowercase().as_str() {
+                    "tcp" => 6,
+                    "udp" => 17,
+                    text => text
+                        .parse()
+                        .map_err(|_| anyhow!("invalid protocol: {}", protocol.text))?,
+                };
+                let mut data: Vec<u8> = vec![];
+                for service in fields.by_ref() {
+                    let port: u16 = service
+                        .text
+                        .parse()
+                        .map_err(|_| anyhow!("invalid service port: {}", service.text))?;
+                    let index = port as usize / 8;
+                    if data.len() <= index {
+                        data.resize(index + 1, 0);
+                    }
+                    data[index] |= 0x80 >> (port % 8);
+                }
+                Record::Wks {
+                    name,
+                    class,
+                    ttl,
+                    addr,
+                    protocol,
+                    data,
+                }
+            }
+            12 => Record::Ptr {
+                name,
+                class,
+                ttl,
+                host: self.name_field(fields)?,
+            },
+            13 => Record::Hinfo {
+                name,
+                class,
+                ttl,
+                cpu: String::from_utf8(character_string(fields)?)?,
+                os: String::from_utf8(character_string(fields)?)?,
+            },
+            14 => Record::Minfo {
+                name,
+                class,
+                ttl,
+                r_mailbox: self.name_field(fields)?,
+                e_mailbox: self.name_field(fields)?,
+            },
+            15 => Record::Mx {
+                name,
+                class,
+                ttl,
+                priority: parse_field(fields, "preference")?,
+                host: self.name_field(fields)?,
+            },
+            16 => {
+                let mut content = vec![CharacterString(character_string(fields)?)];
+                while !fields.as_slice().is_empty() {
+                    content.push(CharacterString(character_string(fields)?));
+                }
+                Record::Txt {
+                    name,
+                    class,
+                    ttl,
+                    content,
+                }
+            }
+            28 => Record::Aaaa {
+                name,
+                class,
+                ttl,
+                addr: parse_field(fields, "IPv6 address")?,
+            },
+            33 => Record::Srv {
+                name,
+                class,
+                ttl,
+                priority: parse_field(fields, "priority")?,
+                weight: parse_field(fields, "weight")?,
+                port: parse_field(fields, "port")?,
+                target: self.name_field(fields)?,
+            },
+            39 => Record::Dname {
+                name,
+                class,
+                ttl,
+                target: self.name_field(fields)?,
+            },
+            64 | 65 => {
+                let priority = parse_field(fields, "priority")?;
+                let target = self.name_field(fields)?;
+                let mut params = vec![];
+                while let Some(param) = fields.next() {
+                    // a quoted value is a separate token from its key
+                    let text = match fields.as_slice().first() {
+                        Some(value) if value.quoted && param.text.ends_with('=') => {
+                            fields.next();
+                            format!("{}\"{}\"", param.text, value.text)
+                        }
+                        _ => param.text.clone(),
+                    };
+                    let param = SvcParam::from_str(&text)
+                        .with_context(|| format!("invalid service parameter: {text}"))?;
+                    params.push(param);
+                }
+                if code == 64 {
+                    Record::Svcb {
+                        name,
+                        class,
+                        ttl,
+                        priority,
+                        target,
+                        params,
+                    }
+                } else {
+                    Record::Https {
+                        name,
+                        class,
+                        ttl,
+                        priority,
+                        target,
+                        params,
+                    }
+                }
+            }
+            257 => {
+                let flags = parse_field(fields, "flags")?;
+                let tag = String::from_utf8(unescape(&next_field(fields)?.text)?)?;
+                let value = String::from_utf8(unescape(&next_field(fields)?.text)?)?;
+                Record::Caa {
+                    name,
+                    class,
+                    ttl,
+                    flags,
+                    tag,
+                    value,
+                }
+            }
+            _ => {
+                bail!("record data for {r_type} must be in the generic format (\\# <length> <hex>)")
+            }
+        };
+
+        if let Some(field) = fields.next() {
+            bail!("unexpected record data: {}", field.text);
+        }
+
+        Ok(record)
+    }
+
+    /// Parses the next token of record data as a name.
+    fn name_field(&self, fields: &mut Iter<'_, Token>) -> Result<Name> {
+        self.parse_name(&next_field(fields)?.text)
+    }
+
+    /// Parses the next token of record data as a TTL-like duration.
+    fn ttl_field(&self, fields: &mut Iter<'_, Token>) -> Result<u32> {
+        let field = next_field(fields)?;
+        parse_ttl(&field.text).with_context(|| format!("invalid duration: {}", field.text))
+    }
+}
+
+/// Parses a zone in master file format.
+///
+/// Relative names are resolved against `origin` until a `$ORIGIN` directive
+/// changes it, and `$INCLUDE` paths are resolved against `dir`. The name of
+/// the zone is the owner of its SOA record, or `origin` if there is none.
+pub(crate) fn parse(input: &str, origin: Option<Name>, dir: &Path) -> Result<Zone> {
+    let mut parser = Parser {
+        origin: origin.clone(),
+        default_ttl: None,
+        last_ttl: None,
+        last_owner: None,
+        last_class: Class::In,
+        dir: dir.to_owned(),
+        depth: 0,
+        generated: 0,
+        records: vec![],
+    };
+    parser.parse(input)?;
+
+    let name = parser
+        .records
+        .iter()
+        .find(|r| matches!(r, Record::Soa { .. }))
+        .map(|r| r.name().clone())
+        .or(origin)
+        .context("zone has no SOA record and no origin")?;
+
+    Ok(Zone::new(name, parser.records))
+}
+
+/// Writes a zone in canonical master file form.
+///
+/// Each record is written on a line of its own with a fully qualified owner,
+/// an explicit TTL and an explicit class. The SOA record comes first.
+pub(crate) fn write(zone: &Zone) -> String {
+    let mut output = format!("$ORIGIN {}\n", zone.name);
+
+    let (soa, rest): (Vec<_>, Vec<_>) = zone
+        .records()
+        .partition(|r| matches!(r, Record::Soa { .. }));
+
+    for record in soa.into_iter().chain(rest) {
+        output.push_str(&format!(
+            "{} {} {} {}\n",
+            record.name(),
+            record.ttl(),
+            record.class(),
+            RecordData(record)
+        ));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, str::FromStr};
+
+    use crate::Name;
+
+    use super::{parse, write};
+
+    #[test]
+    fn parse_master_file() {
+        let input = r#"
+$ORIGIN example.com.
+$TTL 1h
+@   IN  SOA ns admin (
+            2024010101 ; serial
+            2h 1h 2w
+            300 )
+    IN  NS  ns
+    60  MX  10 mail.example.com.
+ns      A   192.0.2.1
+mail    IN 120 A 192.0.2.2
+        AAAA 2001:db8::2
+txt     TXT "hello; world" "a\"b" plain
+e\115c TXT \# 4 03616263
+$GENERATE 1-2 host-${0,3,d} A 10.0.0.$
+"#;
+        let zone = parse(input, None, Path::new(".")).unwrap();
+        let records: Vec<_> = zone.records().map(|r| r.to_string()).collect();
+        assert_eq!(
+            records,
+            [
+                "example.com. IN 3600 SOA ns.example.com. admin.example.com. 2024010101 7200 3600 1209600 300",
+                "example.com. IN 3600 NS ns.example.com.",
+                "example.com. IN 60 MX 10 mail.example.com.",
+                "esc.example.com. IN 3600 TXT \"abc\"",
+                "host-001.example.com. IN 3600 A 10.0.0.1",
+                "host-002.example.com. IN 3600 A 10.0.0.2",
+                "mail.example.com. IN 120 A 192.0.2.2",
+                "mail.example.com. IN 3600 AAAA 2001:db8::2",
+                "ns.example.com. IN 3600 A 192.0.2.1",
+                "txt.example.com. IN 3600 TXT \"hello; world\" \"a\\\"b\" \"plain\"",
+            ]
+        );
+        assert_eq!(zone.name, Name::from_str("example.com.").unwrap());
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let input = r#"
+$ORIGIN example.com.
+www 300 IN CNAME @
+@ 300 IN SOA ns admin 1 2 3 4 5
+@ 300 IN HINFO "x86 64" linux
+@ 300 IN CAA 0 issue "ca.example.net"
+_svc 300 IN SVCB 1 . alpn="h2,h3" port=8443
+@ 300 IN TYPE1234 \# 2 abcd
+"#;
+        let zone = parse(input, None, Path::new(".")).unwrap();
+        let output = write(&zone);
+        assert_eq!(
+            output,
+            "$ORIGIN example.com.\n\
+             example.com. 300 IN SOA ns.example.com. admin.example.com. 1 2 3 4 5\n\
+             example.com. 300 IN HINFO \"x86 64\" \"linux\"\n\
+             example.com. 300 IN CAA 0 issue \"ca.example.net\"\n\
+             example.com. 300 IN TYPE1234 \\# 2 abcd\n\
+             _svc.example.com. 300 IN SVCB 1 . alpn=h2,h3 port=8443\n\
+             www.example.com. 300 IN CNAME example.com.\n"
+        );
+
+        let reparsed = parse(&output, None, Path::new(".")).unwrap();
+        assert_eq!(reparsed.records().count(), zone.records().count());
+        assert_eq!(write(&reparsed), output);
+    }
+
+    #[test]
+    fn generate_range_is_limited() {
+        let input = "$ORIGIN example.com.\n$GENERATE 0-4294967295 host-$ A 192.0.2.1\n";
+        let err = parse(input, None, Path::new(".")).err().unwrap();
+        assert!(format!("{err:#}").contains("exceeds 1000000 records"));
+
+        let input = "$ORIGIN example.com.\n$GENERATE 1-1000001 host-$ A 192.0.2.1\n";
+        assert!(parse(input, None, Path::new(".")).is_err());
+
+        // the limit applies to all directives together
+        let input = "$ORIGIN example.com.\n\
+                     $GENERATE 1-600000 a-$ 60 A 192.0.2.1\n\
+                     $GENERATE 1-400001 b-$ 60 A 192.0.2.1\n";
+        let err = parse(input, None, Path::new(".")).err().unwrap();
+        let err = format!("{err:#}");
+        assert!(err.contains("line 3") && err.contains("exceeds 1000000 records"));
+    }
+}
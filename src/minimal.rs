@@ -3,7 +3,7 @@ use std::{
     net::{Ipv4Addr, Ipv6Addr},
 };
 
-use dex::{Name, Record};
+use dex::{generic_data, CharacterString, Name, Record, SvcParam};
 use serde::Serialize;
 
 /// A minimal representation of a record.
@@ -55,9 +55,36 @@ pub enum MinimalRecord {
     /// Mail exchange record.
     Mx { priority: u16, host: Name },
     /// Text record.
-    Txt { content: String },
+    Txt { content: Vec<CharacterString> },
     /// IPv6 address record.
     Aaaa { addr: Ipv6Addr },
+    /// Service location record.
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: Name,
+    },
+    /// Delegation name record.
+    Dname { target: Name },
+    /// Certification authority authorization record.
+    Caa {
+        flags: u8,
+        tag: String,
+        value: String,
+    },
+    /// Service binding record.
+    Svcb {
+        priority: u16,
+        target: Name,
+        params: Vec<SvcParam>,
+    },
+    /// HTTPS service binding record.
+    Https {
+        priority: u16,
+        target: Name,
+        params: Vec<SvcParam>,
+    },
     /// EDNS options record.
     Opt {
         max_response_size: u16,
@@ -66,6 +93,9 @@ pub enum MinimalRecord {
         dnssec_ok: bool,
         data: Vec<u8>,
     },
+    /// Record of a type without dedicated support, with its data in the
+    /// generic format.
+    Unknown { r_type: u16, data: String },
 }
 
 impl From<Record> for MinimalRecord {
@@ -121,6 +151,42 @@ impl From<Record> for MinimalRecord {
             Record::Mx { priority, host, .. } => MinimalRecord::Mx { priority, host },
             Record::Txt { content, .. } => MinimalRecord::Txt { content },
             Record::Aaaa { addr, .. } => MinimalRecord::Aaaa { addr },
+            Record::Srv {
+                priority,
+                weight,
+                port,
+                target,
+                ..
+            } => MinimalRecord::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            },
+            Record::Dname { target, .. } => MinimalRecord::Dname { target },
+            Record::Caa {
+                flags, tag, value, ..
+            } => MinimalRecord::Caa { flags, tag, value },
+            Record::Svcb {
+                priority,
+                target,
+                params,
+                ..
+            } => MinimalRecord::Svcb {
+                priority,
+                target,
+                params,
+            },
+            Record::Https {
+                priority,
+                target,
+                params,
+                ..
+            } => MinimalRecord::Https {
+                priority,
+                target,
+                params,
+            },
             Record::Opt {
                 max_response_size,
                 extended_rcode,
@@ -135,18 +201,24 @@ impl From<Record> for MinimalRecord {
                 dnssec_ok,
                 data,
             },
+            Record::Unknown { r_type, data, .. } => MinimalRecord::Unknown {
+                r_type,
+                data: generic_data::to_string(&data),
+            },
         }
     }
 }
 
 impl Display for MinimalRecord {
+    /// Names are written with the same formatter, so the alternate form
+    /// (`{:#}`) shows them with Unicode labels.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             MinimalRecord::A { addr, .. } => write!(f, "{addr}"),
-            MinimalRecord::Ns { host, .. } => write!(f, "{host}"),
-            MinimalRecord::Md { host, .. } => write!(f, "{host}"),
-            MinimalRecord::Mf { host, .. } => write!(f, "{host}"),
-            MinimalRecord::Cname { host, .. } => write!(f, "{host}"),
+            MinimalRecord::Ns { host, .. } => host.fmt(f),
+            MinimalRecord::Md { host, .. } => host.fmt(f),
+            MinimalRecord::Mf { host, .. } => host.fmt(f),
+            MinimalRecord::Cname { host, .. } => host.fmt(f),
             MinimalRecord::Soa {
                 origin,
                 mailbox,
@@ -156,13 +228,15 @@ impl Display for MinimalRecord {
                 expire,
                 minimum,
                 ..
-            } => write!(
-                f,
-                "{origin} {mailbox} {version} {refresh} {retry} {expire} {minimum}"
-            ),
-            MinimalRecord::Mb { host, .. } => write!(f, "{host}"),
-            MinimalRecord::Mg { host, .. } => write!(f, "{host}"),
-            MinimalRecord::Mr { host, .. } => write!(f, "{host}"),
+            } => {
+                origin.fmt(f)?;
+                write!(f, " ")?;
+                mailbox.fmt(f)?;
+                write!(f, " {version} {refresh} {retry} {expire} {minimum}")
+            }
+            MinimalRecord::Mb { host, .. } => host.fmt(f),
+            MinimalRecord::Mg { host, .. } => host.fmt(f),
+            MinimalRecord::Mr { host, .. } => host.fmt(f),
             MinimalRecord::Null { data, .. } => write!(f, "{data:x?}"),
             MinimalRecord::Wks {
                 addr,
@@ -170,17 +244,56 @@ impl Display for MinimalRecord {
                 data,
                 ..
             } => write!(f, "{addr} {protocol} {data:x?}"),
-            MinimalRecord::Ptr { host, .. } => write!(f, "{host}"),
+            MinimalRecord::Ptr { host, .. } => host.fmt(f),
             MinimalRecord::Hinfo { cpu, os, .. } => write!(f, "{cpu} {os}"),
             MinimalRecord::Minfo {
                 r_mailbox,
                 e_mailbox,
                 ..
-            } => write!(f, "{r_mailbox} {e_mailbox}"),
-            MinimalRecord::Mx { priority, host, .. } => write!(f, "{priority} {host}"),
-            MinimalRecord::Txt { content, .. } => write!(f, "{content}"),
+            } => {
+                r_mailbox.fmt(f)?;
+                write!(f, " ")?;
+                e_mailbox.fmt(f)
+            }
+            MinimalRecord::Mx { priority, host, .. } => {
+                write!(f, "{priority} ")?;
+                host.fmt(f)
+            }
+            MinimalRecord::Txt { content, .. } => {
+                let content: Vec<_> = content.iter().map(|s| s.to_string()).collect();
+                write!(f, "{}", content.join(" "))
+            }
             MinimalRecord::Aaaa { addr, .. } => write!(f, "{addr}"),
+            MinimalRecord::Srv {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                write!(f, "{priority} {weight} {port} ")?;
+                target.fmt(f)
+            }
+            MinimalRecord::Dname { target } => target.fmt(f),
+            MinimalRecord::Caa { flags, tag, value } => {
+                let value = CharacterString::quote(value.as_bytes());
+                write!(f, "{flags} {tag} {value}")
+            }
+            MinimalRecord::Svcb {
+                priority,
+                target,
+                params,
+            }
+            | MinimalRecord::Https {
+                priority,
+                target,
+                params,
+            } => {
+                write!(f, "{priority} ")?;
+                target.fmt(f)?;
+                params.iter().try_for_each(|p| write!(f, " {p}"))
+            }
             MinimalRecord::Opt { data, .. } => write!(f, "{data:x?}"),
+            MinimalRecord::Unknown { r_type, data } => write!(f, "TYPE{r_type} {data}"),
         }
     }
 }
@@ -0,0 +1,151 @@
+use std::{fs, path::Path, str::FromStr, time::Duration};
+
+use anyhow::{Context, Result};
+
+use crate::Name;
+
+/// Maximum number of nameservers that are used (as in glibc).
+const MAX_NAMESERVERS: usize = 3;
+
+/// Maximum timeout in seconds (as in glibc).
+const MAX_TIMEOUT: u64 = 30;
+
+/// Maximum number of attempts (as in glibc).
+const MAX_ATTEMPTS: usize = 5;
+
+/// Maximum value of ndots (as in glibc).
+const MAX_NDOTS: usize = 15;
+
+/// Resolver configuration, as found in resolv.conf(5).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvConf {
+    /// Nameservers to send requests to, in order of preference.
+    pub nameservers: Vec<String>,
+    /// Time to wait for a response to the first attempt at each nameserver.
+    /// The time is doubled for each further attempt.
+    pub timeout: Duration,
+    /// Number of times each nameserver is tried.
+    pub attempts: usize,
+    /// Whether to start with a random nameserver rather than the first, to
+    /// spread the load across nameservers.
+    pub rotate: bool,
+    /// Domains that relative names are looked up in, in order.
+    pub search: Vec<Name>,
+    /// Number of dots that a relative name must contain to be looked up as
+    /// an absolute name before the search list is tried.
+    pub ndots: usize,
+    /// Whether to include an OPT record in requests (EDNS(0)).
+    pub edns0: bool,
+}
+
+impl Default for ResolvConf {
+    fn default() -> Self {
+        Self {
+            nameservers: vec![],
+            timeout: Duration::from_secs(5),
+            attempts: 2,
+            rotate: false,
+            search: vec![],
+            ndots: 1,
+            edns0: false,
+        }
+    }
+}
+
+impl ResolvConf {
+    /// Parses a ResolvConf from the contents of a resolv.conf file.
+    ///
+    /// The `nameserver`, `search` and `domain` lines and the `timeout:`,
+    /// `attempts:`, `rotate`, `ndots:` and `edns0` options are used. Like the
+    /// system resolver, this ignores lines that it does not understand, uses
+    /// at most three nameservers, caps the timeout, the number of attempts
+    /// and ndots, and uses whichever of `search` and `domain` comes last.
+    pub fn parse(input: &str) -> Self {
+        let mut conf = Self::default();
+
+        for line in input.lines() {
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("nameserver") => {
+                    if let Some(addr) = parts.next() {
+                        if conf.nameservers.len() < MAX_NAMESERVERS {
+                            conf.nameservers.push(addr.to_owned());
+                        }
+                    }
+                }
+                Some("search" | "domain") => {
+                    conf.search = parts.filter_map(|d| Name::from_str(d).ok()).collect();
+                }
+                Some("options") => parts.for_each(|option| conf.set_option(option)),
+                _ => {}
+            }
+        }
+
+        conf
+    }
+
+    /// Loads a ResolvConf from a file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let input = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(Self::parse(&input))
+    }
+
+    /// Applies an option from an `options` line.
+    fn set_option(&mut self, option: &str) {
+        if let Some(timeout) = option.strip_prefix("timeout:") {
+            if let Ok(timeout) = timeout.parse::<u64>() {
+                self.timeout = Duration::from_secs(timeout.clamp(1, MAX_TIMEOUT));
+            }
+        } else if let Some(attempts) = option.strip_prefix("attempts:") {
+            if let Ok(attempts) = attempts.parse::<usize>() {
+                self.attempts = attempts.clamp(1, MAX_ATTEMPTS);
+            }
+        } else if let Some(ndots) = option.strip_prefix("ndots:") {
+            if let Ok(ndots) = ndots.parse::<usize>() {
+                self.ndots = ndots.min(MAX_NDOTS);
+            }
+        } else if option == "rotate" {
+            self.rotate = true;
+        } else if option == "edns0" {
+            self.edns0 = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, time::Duration};
+
+    use crate::{Name, ResolvConf};
+
+    #[test]
+    fn parse_resolv_conf() {
+        let conf = ResolvConf::parse(
+            "# generated\n\
+             nameserver 192.0.2.1\n\
+             nameserver 2001:db8::1\n\
+             domain example.org\n\
+             search example.com corp.example.com\n\
+             options ndots:2 timeout:60 attempts:3 rotate edns0\n\
+             nameserver 192.0.2.2\n\
+             nameserver 192.0.2.3\n",
+        );
+        assert_eq!(conf.nameservers, ["192.0.2.1", "2001:db8::1", "192.0.2.2"]);
+        assert_eq!(conf.timeout, Duration::from_secs(30));
+        assert_eq!(conf.attempts, 3);
+        assert!(conf.rotate);
+        assert_eq!(
+            conf.search,
+            [
+                Name::from_str("example.com.").unwrap(),
+                Name::from_str("corp.example.com.").unwrap()
+            ]
+        );
+        assert_eq!(conf.ndots, 2);
+        assert!(conf.edns0);
+
+        assert_eq!(ResolvConf::parse(""), ResolvConf::default());
+    }
+}
@@ -0,0 +1,614 @@
+use std::{fmt::Display, net::IpAddr, str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::{bail, Result};
+use log::warn;
+
+use crate::{
+    split_unescaped, FallbackTransport, Message, Name, ParseNameError, Question, QuestionClass,
+    QuestionType, Record, ResolvConf, ResponseCode, TcpTransport, Transport, TransportError,
+    UdpTransport,
+};
+
+/// Maximum size of UDP responses when EDNS(0) is used.
+const EDNS_RESPONSE_SIZE: u16 = 4096;
+
+/// Maximum size of UDP responses without EDNS(0).
+const RESPONSE_SIZE: u16 = 512;
+
+/// Creates the transport for an attempt at a nameserver, given the address
+/// of the nameserver and the time to wait for a response.
+type Connect = dyn Fn(&str, Duration) -> Box<dyn Transport> + Send + Sync;
+
+/// A stub resolver, which sends queries to recursive nameservers.
+///
+/// # Example
+///
+/// ```no_run
+/// use dex::{QuestionType, Resolver};
+///
+/// let resolver = Resolver::from_system()?;
+/// for addr in resolver.lookup_ip("example.com")? {
+///     println!("{addr}");
+/// }
+/// let answer = resolver.lookup("example.com", QuestionType::MX)?;
+/// for record in answer.records {
+///     println!("{record}");
+/// }
+/// # anyhow::Ok(())
+/// ```
+#[derive(Clone)]
+pub struct Resolver {
+    conf: ResolvConf,
+    connect: Arc<Connect>,
+}
+
+/// The answer to a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answer {
+    /// The name that the answer is for, i.e., the name that was queried
+    /// after expanding it through the search list.
+    pub name: Name,
+    /// The answer records, including any CNAME records that lead from the
+    /// name to the records of the requested type.
+    pub records: Vec<Record>,
+}
+
+impl Resolver {
+    /// Creates a new Resolver with the given configuration.
+    ///
+    /// Requests are sent over UDP and retried over TCP if the response is
+    /// truncated.
+    pub fn new(conf: ResolvConf) -> Self {
+        let max_response_size = if conf.edns0 {
+            EDNS_RESPONSE_SIZE
+        } else {
+            RESPONSE_SIZE
+        };
+        Self {
+            conf,
+            connect: Arc::new(move |nameserver, timeout| {
+                let udp = UdpTransport::new(nameserver.to_owned(), max_response_size)
+                    .with_timeout(timeout);
+                let tcp = TcpTransport::new(nameserver.to_owned()).with_timeout(timeout);
+                Box::new(FallbackTransport::new(udp, tcp))
+            }),
+        }
+    }
+
+    /// Creates a new Resolver with the configuration of this system, from
+    /// /etc/resolv.conf.
+    pub fn from_system() -> Result<Self> {
+        let conf = ResolvConf::from_file("/etc/resolv.conf")?;
+        if conf.nameservers.is_empty() {
+            bail!("no nameservers found in /etc/resolv.conf");
+        }
+        Ok(Self::new(conf))
+    }
+
+    /// Sets how the transport for an attempt at a nameserver is created,
+    /// given the address of the nameserver and the time to wait for a
+    /// response (UDP with fallback to TCP by default).
+    pub fn with_transport(
+        mut self,
+        connect: impl Fn(&str, Duration) -> Box<dyn Transport> + Send + Sync + 'static,
+    ) -> Self {
+        self.connect = Arc::new(connect);
+        self
+    }
+
+    /// Returns the configuration of the resolver.
+    pub fn conf(&self) -> &ResolvConf {
+        &self.conf
+    }
+
+    /// Looks up records of a type for a name.
+    ///
+    /// Absolute names (ending with a dot) are looked up as they are. Relative
+    /// names are expanded through the search list: names with at least
+    /// `ndots` dots are tried as absolute names first and names with fewer
+    /// dots last (RFC 1535).
+    ///
+    /// Returns the first answer that has records of the requested type.
+    /// Otherwise, returns [`ResolveError::NoData`] if any of the names exist,
+    /// [`ResolveError::Failure`] if a nameserver failed to answer for any of
+    /// them, and [`ResolveError::NameError`] if none exist.
+    pub fn lookup(&self, name: &str, q_type: QuestionType) -> Result<Answer, ResolveError> {
+        let mut result = Err(ResolveError::NoNameservers);
+        for candidate in self.candidates(name)? {
+            match self.query(candidate, q_type.clone()) {
+                Ok(answer) => return Ok(answer),
+                Err(e @ ResolveError::NoData { .. }) => result = Err(e),
+                Err(e @ ResolveError::Failure { .. }) => {
+                    if !matches!(result, Err(ResolveError::NoData { .. })) {
+                        result = Err(e);
+                    }
+                }
+                Err(e @ ResolveError::NameError { .. }) => {
+                    if !matches!(
+                        result,
+                        Err(ResolveError::NoData { .. } | ResolveError::Failure { .. })
+                    ) {
+                        result = Err(e);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        result
+    }
+
+    /// Looks up the IPv4 and IPv6 addresses of a host.
+    ///
+    /// The name is expanded through the search list, as by
+    /// [`lookup`](Self::lookup). Returns an error if the host has no
+    /// addresses of either kind.
+    pub fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>, ResolveError> {
+        let v4 = self.lookup(host, QuestionType::A);
+        let v6 = self.lookup(host, QuestionType::AAAA);
+
+        let mut addrs = vec![];
+        let mut error = None;
+        for result in [v4, v6] {
+            match result {
+                Ok(answer) => addrs.extend(answer.records.iter().filter_map(address)),
+                Err(e) => error = error.or(Some(e)),
+            }
+        }
+
+        match error {
+            Some(e) if addrs.is_empty() => Err(e),
+            _ => Ok(addrs),
+        }
+    }
+
+    /// Looks up the names of a host from its address, using PTR records in
+    /// in-addr.arpa or ip6.arpa.
+    pub fn reverse_lookup(&self, addr: IpAddr) -> Result<Vec<Name>, ResolveError> {
+        let answer = self.query(reverse_name(addr), QuestionType::PTR)?;
+        Ok(answer
+            .records
+            .into_iter()
+            .filter_map(|r| match r {
+                Record::Ptr { host, .. } => Some(host),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Sends a request to the configured nameservers until one of them
+    /// responds.
+    ///
+    /// Each round tries every nameserver once, starting with the first one
+    /// (or a random one, if rotation is enabled), and the timeout is doubled
+    /// after each round. Returns the last error if no nameserver responds.
+    pub fn send(&self, request: Message) -> Result<Message, ResolveError> {
+        let nameservers = &self.conf.nameservers;
+        if nameservers.is_empty() {
+            return Err(ResolveError::NoNameservers);
+        }
+
+        let count = nameservers.len();
+        let start = if self.conf.rotate {
+            rand::random::<usize>() % count
+        } else {
+            0
+        };
+
+        let mut last_error = None;
+        for round in 0..self.conf.attempts {
+            let timeout = self.conf.timeout.saturating_mul(1 << round.min(16));
+            for i in 0..count {
+                let nameserver = &nameservers[(start + i) % count];
+                match (self.connect)(nameserver, timeout).send(request.clone()) {
+                    Ok(response) => return Ok(response),
+                    Err(e) => {
+                        warn!("no response from {nameserver}: {e}");
+                        last_error = Some(e);
+                    }
+                }
+            }
+        }
+
+        Err(ResolveError::NoResponse {
+            nameservers: nameservers.clone(),
+            attempts: self.conf.attempts,
+            source: last_error,
+        })
+    }
+
+    /// Returns the absolute names to try for a name, in order.
+    fn candidates(&self, name: &str) -> Result<Vec<Name>, ResolveError> {
+        let labels = split_unescaped(name, '.');
+        let absolute = Name::from_str(name).map_err(|source| ResolveError::InvalidName {
+            name: name.to_owned(),
+            source,
+        })?;
+        if labels.last() == Some(&"") {
+            return Ok(vec![absolute]);
+        }
+
+        let mut candidates: Vec<_> = self
+            .conf
+            .search
+            .iter()
+            .filter_map(|domain| absolute.replace_suffix(&Name::root(), domain))
+            .collect();
+        if labels.len() > self.conf.ndots {
+            candidates.insert(0, absolute);
+        } else {
+            candidates.push(absolute);
+        }
+        Ok(candidates)
+    }
+
+    /// Queries for records of a type for an absolute name.
+    fn query(&self, name: Name, q_type: QuestionType) -> Result<Answer, ResolveError> {
+        let code = q_type.code();
+        let response = self.send(self.request(name.clone(), q_type))?;
+
+        match response.header.resp_code {
+            ResponseCode::Success => {}
+            ResponseCode::NameError => return Err(ResolveError::NameError { name }),
+            code => return Err(ResolveError::Failure { name, code }),
+        }
+
+        let records = response.answer_records;
+        if !records.iter().any(|r| r.code() == code) {
+            return Err(ResolveError::NoData { name });
+        }
+        Ok(Answer { name, records })
+    }
+
+    /// Builds a recursive request for a single question.
+    fn request(&self, name: Name, q_type: QuestionType) -> Message {
+        let mut request = Message::new();
+        request.header.recursion_desired = true;
+        request.header.question_count = 1;
+        request.questions = vec![Question {
+            name,
+            q_type,
+            q_class: QuestionClass::In,
+        }];
+
+        if self.conf.edns0 {
+            request.header.additional_count = 1;
+            request.additional_records = vec![Record::Opt {
+                name: Name::root(),
+                max_response_size: EDNS_RESPONSE_SIZE,
+                extended_rcode: 0,
+                version: 0,
+                dnssec_ok: false,
+                data: vec![],
+            }];
+        }
+
+        request
+    }
+}
+
+/// Returns the address of an A or AAAA record.
+fn address(record: &Record) -> Option<IpAddr> {
+    match record {
+        Record::A { addr, .. } => Some(IpAddr::V4(*addr)),
+        Record::Aaaa { addr, .. } => Some(IpAddr::V6(*addr)),
+        _ => None,
+    }
+}
+
+/// Returns the name that the PTR records for an address are found at.
+fn reverse_name(addr: IpAddr) -> Name {
+    let text = match addr {
+        IpAddr::V4(addr) => {
+            let [a, b, c, d] = addr.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa.")
+        }
+        IpAddr::V6(addr) => {
+            let mut text = String::new();
+            for byte in addr.octets().iter().rev() {
+                text.push_str(&format!("{:x}.{:x}.", byte & 0xf, byte >> 4));
+            }
+            text + "ip6.arpa."
+        }
+    };
+    Name::from_str(&text).unwrap()
+}
+
+/// An error that occurred while resolving a name.
+#[derive(Debug)]
+pub enum ResolveError {
+    /// The name to look up is not a valid name.
+    ///
+    /// The reason is part of the message, so it is not also the source of
+    /// the error.
+    InvalidName {
+        name: String,
+        source: ParseNameError,
+    },
+    /// No nameservers are configured.
+    NoNameservers,
+    /// None of the nameservers responded.
+    NoResponse {
+        nameservers: Vec<String>,
+        attempts: usize,
+        source: Option<TransportError>,
+    },
+    /// The name does not exist.
+    NameError { name: Name },
+    /// The name exists but has no records of the requested type.
+    NoData { name: Name },
+    /// The nameserver failed to answer the query, e.g., because it refused
+    /// it or could not resolve it.
+    Failure { name: Name, code: ResponseCode },
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ResolveError::*;
+
+        match self {
+            InvalidName { name, source } => write!(f, "invalid name {name:?}: {source}"),
+            NoNameservers => write!(f, "no nameservers configured"),
+            NoResponse {
+                nameservers,
+                attempts,
+                ..
+            } => write!(
+                f,
+                "no response from {} after {attempts} attempt(s) each",
+                nameservers.join(", ")
+            ),
+            NameError { name } => write!(f, "{name} does not exist"),
+            NoData { name } => write!(f, "{name} has no records of the requested type"),
+            Failure { name, code } => write!(f, "failed to resolve {name}: {code}"),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ResolveError::NoResponse {
+                source: Some(e), ..
+            } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, SocketAddr},
+        str::FromStr,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use crate::{
+        Class, Message, MockTransport, Name, QuestionType, Record, ResolvConf, ResolveError,
+        Resolver, ResponseCode, TransportError,
+    };
+
+    /// Creates a response to the last request that a transport received.
+    fn response(transport: &MockTransport, resp_code: ResponseCode, records: Vec<Record>) {
+        let mut response = Message::new();
+        response.header.is_response = true;
+        response.header.resp_code = resp_code;
+        response.header.answer_count = records.len() as u16;
+        response.answer_records = records;
+        transport.push_response(response);
+    }
+
+    fn a_record(name: &str, addr: [u8; 4]) -> Record {
+        Record::A {
+            name: Name::from_str(name).unwrap(),
+            class: Class::In,
+            ttl: 60,
+            addr: addr.into(),
+        }
+    }
+
+    fn resolver(conf: ResolvConf, transport: &MockTransport) -> Resolver {
+        let transport = transport.clone();
+        Resolver::new(conf).with_transport(move |_, _| Box::new(transport.clone()))
+    }
+
+    /// Returns the names that a transport was asked about.
+    fn queried(transport: &MockTransport) -> Vec<String> {
+        transport
+            .requests()
+            .iter()
+            .map(|r| r.questions[0].name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn search_list_and_ndots() {
+        let conf = ResolvConf {
+            nameservers: vec!["192.0.2.53".to_owned()],
+            search: vec![
+                Name::from_str("corp.example.").unwrap(),
+                Name::from_str("example.").unwrap(),
+            ],
+            ndots: 1,
+            ..ResolvConf::default()
+        };
+        let transport = MockTransport::new();
+        let resolver = resolver(conf, &transport);
+
+        // a name with fewer dots than ndots is tried in the search list first
+        response(&transport, ResponseCode::NameError, vec![]);
+        response(
+            &transport,
+            ResponseCode::Success,
+            vec![a_record("www.example.", [192, 0, 2, 1])],
+        );
+        let answer = resolver.lookup("www", QuestionType::A).unwrap();
+        assert_eq!(answer.name, Name::from_str("www.example.").unwrap());
+        assert_eq!(queried(&transport), ["www.corp.example.", "www.example."]);
+
+        // a name with enough dots is tried as it is first
+        response(&transport, ResponseCode::NameError, vec![]);
+        response(&transport, ResponseCode::Success, vec![]);
+        response(&transport, ResponseCode::NameError, vec![]);
+        let err = resolver.lookup("host.test", QuestionType::A).unwrap_err();
+        assert!(
+            matches!(err, ResolveError::NoData { name } if name.to_string() == "host.test.corp.example.")
+        );
+        assert_eq!(
+            queried(&transport)[2..],
+            [
+                "host.test.",
+                "host.test.corp.example.",
+                "host.test.example."
+            ]
+        );
+
+        // an absolute name is not expanded
+        response(&transport, ResponseCode::NameError, vec![]);
+        let err = resolver.lookup("www.", QuestionType::A).unwrap_err();
+        assert!(matches!(err, ResolveError::NameError { .. }));
+        assert_eq!(transport.requests().len(), 6);
+        assert!(transport.requests()[5].additional_records.is_empty());
+
+        // a name that a nameserver fails to answer for is skipped
+        response(&transport, ResponseCode::ServerFailure, vec![]);
+        response(
+            &transport,
+            ResponseCode::Success,
+            vec![a_record("mail.example.", [192, 0, 2, 2])],
+        );
+        let answer = resolver.lookup("mail", QuestionType::A).unwrap();
+        assert_eq!(answer.name, Name::from_str("mail.example.").unwrap());
+
+        // but the failure is returned if no name exists
+        response(&transport, ResponseCode::Refused, vec![]);
+        response(&transport, ResponseCode::NameError, vec![]);
+        response(&transport, ResponseCode::NameError, vec![]);
+        let err = resolver.lookup("gone", QuestionType::A).unwrap_err();
+        assert!(matches!(
+            err,
+            ResolveError::Failure { name, code: ResponseCode::Refused }
+                if name.to_string() == "gone.corp.example."
+        ));
+        assert_eq!(
+            queried(&transport)[6..],
+            [
+                "mail.corp.example.",
+                "mail.example.",
+                "gone.corp.example.",
+                "gone.example.",
+                "gone."
+            ]
+        );
+
+        // an invalid name is reported with the reason, without a query
+        let err = resolver.lookup("bad..name", QuestionType::A).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"invalid name "bad..name": name contains an empty label"#
+        );
+        assert_eq!(transport.requests().len(), 11);
+    }
+
+    #[test]
+    fn ip_and_reverse_lookups() {
+        let conf = ResolvConf {
+            nameservers: vec!["192.0.2.53".to_owned()],
+            edns0: true,
+            ..ResolvConf::default()
+        };
+        let transport = MockTransport::new();
+        let resolver = resolver(conf, &transport);
+
+        response(
+            &transport,
+            ResponseCode::Success,
+            vec![a_record("example.com.", [192, 0, 2, 1])],
+        );
+        response(&transport, ResponseCode::Success, vec![]);
+        let addrs = resolver.lookup_ip("example.com.").unwrap();
+        assert_eq!(addrs, [IpAddr::from([192, 0, 2, 1])]);
+        assert_eq!(transport.requests()[0].additional_records.len(), 1);
+
+        response(
+            &transport,
+            ResponseCode::Success,
+            vec![Record::Ptr {
+                name: Name::from_str("1.2.0.192.in-addr.arpa.").unwrap(),
+                class: Class::In,
+                ttl: 60,
+                host: Name::from_str("example.com.").unwrap(),
+            }],
+        );
+        let names = resolver
+            .reverse_lookup(IpAddr::from([192, 0, 2, 1]))
+            .unwrap();
+        assert_eq!(names, [Name::from_str("example.com.").unwrap()]);
+
+        response(&transport, ResponseCode::NameError, vec![]);
+        let addr = IpAddr::from_str("2001:db8::1").unwrap();
+        assert!(resolver.reverse_lookup(addr).is_err());
+        assert_eq!(
+            queried(&transport)[2..],
+            [
+                "1.2.0.192.in-addr.arpa.",
+                "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa."
+            ]
+        );
+    }
+
+    #[test]
+    fn retries_back_off_across_nameservers() {
+        let conf = ResolvConf {
+            nameservers: vec!["a".to_owned(), "b".to_owned()],
+            timeout: Duration::from_secs(1),
+            attempts: 2,
+            ..ResolvConf::default()
+        };
+        let a = MockTransport::new();
+        let b = MockTransport::new();
+        let tries = Arc::new(Mutex::new(vec![]));
+        let resolver = Resolver::new(conf).with_transport({
+            let (a, b, tries) = (a.clone(), b.clone(), tries.clone());
+            move |nameserver, timeout| {
+                tries
+                    .lock()
+                    .unwrap()
+                    .push((nameserver.to_owned(), timeout.as_secs()));
+                match nameserver {
+                    "a" => Box::new(a.clone()),
+                    _ => Box::new(b.clone()),
+                }
+            }
+        });
+
+        let timeout_error = || TransportError::Timeout {
+            addr: SocketAddr::from(([192, 0, 2, 1], 53)),
+        };
+        for _ in 0..2 {
+            a.push_error(timeout_error());
+            b.push_error(timeout_error());
+        }
+        let err = resolver.send(Message::new()).unwrap_err();
+        assert_eq!(
+            std::mem::take(&mut *tries.lock().unwrap()),
+            [
+                ("a".into(), 1),
+                ("b".into(), 1),
+                ("a".into(), 2),
+                ("b".into(), 2)
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "no response from a, b after 2 attempt(s) each"
+        );
+
+        a.push_error(timeout_error());
+        b.push_response(Message::new());
+        assert!(resolver.send(Message::new()).is_ok());
+        assert_eq!(tries.lock().unwrap().len(), 2);
+        assert_eq!(b.requests().len(), 3);
+    }
+}
@@ -1,161 +1,1587 @@
-use std::fs;
+mod config;
+mod logger;
 
-use dex::{Bytes, Message, OperationCode, QuestionType, Record, ResponseCode, Zone};
+use std::{
+    future::{self, Future},
+    io::{self, ErrorKind},
+    mem,
+    net::SocketAddr,
+    path::PathBuf,
+    process::ExitCode,
+    str::FromStr,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
 
-fn main() {
-    Server::start();
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use config::{Config, LogLevel, ZoneConfig};
+use dex::{Bytes, Header, Lookup, Message, Name, OperationCode, Record, ResponseCode, Zone};
+use log::{debug, error, info, warn};
+use logger::init_logger;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
+    sync::{oneshot, Semaphore},
+    task::JoinSet,
+    time::timeout,
+};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "An authoritative DNS server.", max_term_width = 80)]
+struct Cli {
+    /// Path to a configuration file in TOML format.
+    ///
+    /// Options given on the command line take precedence over those in the
+    /// configuration file.
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// Address (including port) to listen on, e.g., 0.0.0.0:53 or [::]:53.
+    /// May be given more than once. (default: 0.0.0.0:5380)
+    #[arg(short, long)]
+    listen: Vec<SocketAddr>,
+    /// Path to a zone file to serve, in TOML format (with a .toml extension)
+    /// or master file format (with any other extension), optionally followed
+    /// by =ORIGIN for master files without an $ORIGIN directive. May be given
+    /// more than once.
+    #[arg(short, long, value_name = "PATH[=ORIGIN]")]
+    zone: Vec<ZoneConfig>,
+    /// Maximum level of log messages to print. (default: info)
+    #[arg(long)]
+    log_level: Option<LogLevel>,
+    /// Number of threads that serve queries on each UDP address. With more
+    /// than one, each thread has a socket of its own (using SO_REUSEPORT where
+    /// available). (default: 1)
+    #[arg(long)]
+    workers: Option<usize>,
+}
+
+impl Cli {
+    /// Builds the server configuration from the configuration file (if any)
+    /// and the command line options.
+    fn into_config(self) -> Result<Config> {
+        let mut config = match &self.config {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+
+        if !self.listen.is_empty() {
+            config.listen = self.listen;
+        }
+        if !self.zone.is_empty() {
+            config.zones = self.zone;
+        }
+        if let Some(log_level) = self.log_level {
+            config.log_level = log_level;
+        }
+        if let Some(workers) = self.workers {
+            config.workers = workers;
+        }
+
+        config.validate().context("invalid configuration")?;
+        Ok(config)
+    }
+}
+
+fn main() -> ExitCode {
+    let config = match Cli::parse().into_config() {
+        Ok(config) => config,
+        Err(e) => {
+            init_logger(log::LevelFilter::Error);
+            error!("{e:?}");
+            return ExitCode::from(1);
+        }
+    };
+
+    init_logger(config.log_level.into());
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            error!("failed to start runtime: {e}");
+            return ExitCode::from(1);
+        }
+    };
+
+    match runtime.block_on(Server::start(&config)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("{e:?}");
+            ExitCode::from(1)
+        }
+    }
 }
 
 /// A DNS server.
 struct Server {
-    zone: Zone,
+    zones: Vec<Zone>,
 }
 
 impl Server {
     /// Starts a new DNS server.
-    fn start() {
-        let zone_file = "zone.toml";
-        println!("loading zone data from {zone_file}");
-        let zone_data = fs::read_to_string(zone_file).unwrap();
-        let zone = Zone::from_toml(&zone_data).unwrap();
-        let server = Self { zone };
-
-        println!("listening on port 5380");
-        let socket = std::net::UdpSocket::bind("0.0.0.0:5380").unwrap();
+    ///
+    /// Returns an error if a zone cannot be loaded or an address cannot be
+    /// bound. Otherwise, serves queries indefinitely.
+    async fn start(config: &Config) -> Result<()> {
+        let mut zones: Vec<Zone> = vec![];
+        for zone_config in &config.zones {
+            let zone_file = &zone_config.path;
+            info!("loading zone data from {}", zone_file.display());
+            let zone = Zone::from_file(zone_file, zone_config.origin.clone())
+                .with_context(|| format!("failed to load zone {}", zone_file.display()))?;
+            if zones.iter().any(|z| z.name() == zone.name()) {
+                bail!("zone {} is configured more than once", zone.name());
+            }
+            zones.push(zone);
+        }
+        let server = Arc::new(Self { zones });
+
+        let mut sockets = vec![];
+        let mut listeners = vec![];
+        for addr in &config.listen {
+            let udp_sockets = bind_udp(*addr, config.workers)
+                .with_context(|| format!("failed to bind {addr}"))?;
+            let listener = bind_tcp(*addr).with_context(|| format!("failed to bind {addr}"))?;
+            info!("listening on {addr} (UDP and TCP)");
+            sockets.extend(udp_sockets);
+            listeners.push(listener);
+        }
+
+        let tcp_limits = TcpLimits {
+            idle_timeout: Duration::from_secs(config.tcp_idle_timeout),
+            connections: Arc::new(Semaphore::new(config.tcp_max_connections)),
+        };
+
+        let mut tasks = JoinSet::new();
+        for socket in sockets {
+            if config.workers == 1 {
+                let serving = server.clone().serve_udp(UdpSocket::from_std(socket)?);
+                tasks.spawn(async move {
+                    serving.await;
+                    Ok(())
+                });
+            } else {
+                let server = server.clone();
+                let (stopped, stop) = oneshot::channel();
+                let worker = thread::Builder::new()
+                    .name("udp-worker".to_owned())
+                    .spawn(move || {
+                        let result = server.serve_udp_worker(socket, future::pending());
+                        let _ = stopped.send(());
+                        result
+                    })
+                    .context("failed to start UDP worker")?;
+
+                // a worker serves indefinitely, so one that stops would leave
+                // its share of the queries unanswered (the channel is also
+                // closed if the worker panics)
+                tasks.spawn(async move {
+                    let _ = stop.await;
+                    match worker.join() {
+                        Ok(result) => result.context("UDP worker failed")?,
+                        Err(_) => bail!("UDP worker panicked"),
+                    }
+                    bail!("UDP worker stopped")
+                });
+            }
+        }
+        for listener in listeners {
+            let accepting = server.clone().accept_tcp(listener, tcp_limits.clone());
+            tasks.spawn(async move {
+                accepting.await;
+                Ok(())
+            });
+        }
+        while let Some(result) = tasks.join_next().await {
+            result??;
+        }
+        Ok(())
+    }
+
+    /// Serves queries received on a UDP socket on the calling thread, until
+    /// `shutdown` completes.
+    ///
+    /// The socket is served by a runtime of its own, so that workers do not
+    /// contend with each other or with the TCP connections for threads.
+    /// Returns an error if the runtime cannot be started or serving fails.
+    fn serve_udp_worker(
+        self: Arc<Self>,
+        socket: std::net::UdpSocket,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> io::Result<()> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        // the queries in flight are dropped along with the runtime
+        runtime.block_on(async {
+            let mut tasks = JoinSet::new();
+            tasks.spawn(self.serve_udp(UdpSocket::from_std(socket)?));
+            tasks.spawn(shutdown);
+            tasks.join_next().await.transpose()?;
+            Ok(())
+        })
+    }
+
+    /// Serves queries received on a UDP socket indefinitely.
+    ///
+    /// Each query is served by a task of its own, so that queries are served
+    /// in parallel on the threads of the runtime. At most
+    /// `MAX_UDP_IN_FLIGHT` queries are served at once; further queries wait
+    /// in the socket buffer.
+    async fn serve_udp(self: Arc<Self>, socket: UdpSocket) {
+        let socket = Arc::new(socket);
+        let in_flight = Arc::new(Semaphore::new(MAX_UDP_IN_FLIGHT));
+        loop {
+            let permit = in_flight.clone().acquire_owned().await.unwrap();
+
+            let mut query_buffer = vec![0; MAX_UDP_PAYLOAD as usize];
+            let (len, addr) = match socket.recv_from(&mut query_buffer).await {
+                Ok(received) => received,
+                Err(e) => {
+                    warn!("failed to receive query: {e}");
+                    continue;
+                }
+            };
+            query_buffer.truncate(len);
+            debug!("received query from {addr}");
+
+            let server = self.clone();
+            let socket = socket.clone();
+            tokio::spawn(async move {
+                let Some(response) = server.handle(&query_buffer, addr, true) else {
+                    return;
+                };
+
+                match socket.send_to(&response, addr).await {
+                    Ok(_) => debug!("returned response to {addr}"),
+                    Err(e) => warn!("failed to send response to {addr}: {e}"),
+                }
+                drop(permit);
+            });
+        }
+    }
+
+    /// Accepts connections on a TCP listener indefinitely.
+    ///
+    /// Each connection is served by a task of its own. Connections beyond
+    /// the configured maximum are closed immediately.
+    async fn accept_tcp(self: Arc<Self>, listener: TcpListener, limits: TcpLimits) {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("failed to accept connection: {e}");
+                    continue;
+                }
+            };
+
+            let Ok(permit) = limits.connections.clone().try_acquire_owned() else {
+                warn!("refused connection from {addr}: too many connections");
+                continue;
+            };
+            debug!("accepted connection from {addr}");
+
+            let server = self.clone();
+            tokio::spawn(async move {
+                match server.serve_tcp(stream, addr, limits.idle_timeout).await {
+                    Ok(()) => debug!("closed connection from {addr}"),
+                    Err(e) if e.kind() == ErrorKind::TimedOut => {
+                        debug!("closed idle connection from {addr}")
+                    }
+                    Err(e) => warn!("closed connection from {addr}: {e}"),
+                }
+                drop(permit);
+            });
+        }
+    }
+
+    /// Serves queries received on a TCP connection until the client closes
+    /// the connection or it is idle for too long.
+    ///
+    /// Each message is preceded by its length as a two-byte integer. Clients
+    /// may send several queries without waiting for responses, which are
+    /// returned in the order that the queries were received (RFC 7766).
+    async fn serve_tcp(
+        &self,
+        mut stream: TcpStream,
+        addr: SocketAddr,
+        idle_timeout: Duration,
+    ) -> io::Result<()> {
         loop {
-            let mut query_buffer = [0; 512];
-            let (_, addr) = socket.recv_from(&mut query_buffer).unwrap();
-            println!("received query from {addr}");
+            let len = match with_timeout(idle_timeout, stream.read_u16()).await {
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            let mut query_buffer = vec![0; len as usize];
+            with_timeout(idle_timeout, stream.read_exact(&mut query_buffer)).await?;
+            debug!("received query from {addr}");
+
+            // a query without a valid header cannot be answered, and it is
+            // unclear where the next query starts
+            let Some(response) = self.handle(&query_buffer, addr, false) else {
+                return Ok(());
+            };
+
+            let mut message = (response.len() as u16).to_be_bytes().to_vec();
+            message.extend_from_slice(&response);
+            with_timeout(idle_timeout, stream.write_all(&message)).await?;
 
-            let mut query_bytes = Bytes::from_buf(&query_buffer);
-            let query = Message::from_bytes(&mut query_bytes);
+            debug!("returned response to {addr}");
+        }
+    }
 
-            let response = server.serve(query);
-            println!("response: {:?}", response.header.resp_code);
+    /// Handles a query received from a client.
+    ///
+    /// Returns the encoded response, which is truncated to fit the payload
+    /// size negotiated with the client if the query was received over UDP.
+    /// Returns None if no response should be sent.
+    fn handle(&self, query: &[u8], addr: SocketAddr, is_udp: bool) -> Option<Vec<u8>> {
+        // other operations, such as NOTIFY and UPDATE, are not supported, and
+        // their messages need not decode like queries
+        let header = Header::from_bytes(&mut Bytes::from_buf(query)).ok()?;
 
-            let mut response_bytes = Bytes::new();
-            response.to_bytes(&mut response_bytes);
-            socket.send_to(response_bytes.used(), addr).unwrap();
+        // answering responses would let spoofed or reflected messages bounce
+        // between servers indefinitely
+        if header.is_response {
+            debug!("dropped response from {addr}");
+            return None;
+        }
+        if header.op_code != OperationCode::Query {
+            debug!("unsupported operation {} from {addr}", header.op_code);
+            let response = Self::error_response(query, ResponseCode::NotImplemented)?;
+            return Some(encode_within(response, 512));
+        }
+
+        let mut query_bytes = Bytes::from_buf(query);
+        let (response, max_size) = match Message::from_bytes(&mut query_bytes) {
+            Ok(query) => {
+                let max_size = if is_udp {
+                    udp_payload_size(&query)
+                } else {
+                    u16::MAX as usize
+                };
+                (self.serve(query), max_size)
+            }
+            Err(e) => {
+                warn!("failed to decode query from {addr}: {e}");
+                (Self::error_response(query, ResponseCode::FormatError)?, 512)
+            }
+        };
+        debug!("response: {:?}", response.header.resp_code);
+        Some(encode_within(response, max_size))
+    }
 
-            println!("returned response to sender");
+    /// Builds an error response for a query that could not be decoded or
+    /// served, from the header of the query alone.
+    ///
+    /// Returns None if the header of the query is itself malformed, or if the
+    /// message is a response, in which case it should be dropped.
+    fn error_response(query: &[u8], resp_code: ResponseCode) -> Option<Message> {
+        let mut bytes = Bytes::from_buf(query);
+        let header = Header::from_bytes(&mut bytes).ok()?;
+        if header.is_response {
+            return None;
         }
+
+        let mut response = Message::new();
+        response.header.id = header.id;
+        response.header.is_response = true;
+        response.header.op_code = header.op_code;
+        response.header.recursion_desired = header.recursion_desired;
+        response.header.resp_code = resp_code;
+        Some(response)
     }
 
     /// Serves a DNS query.
     ///
-    /// Returns a DNS response.
+    /// Returns a DNS response. If the query includes an OPT record, the
+    /// response includes an OPT record of its own (RFC 6891).
     fn serve(&self, query: Message) -> Message {
         let mut response = query;
         response.header.is_response = true;
 
-        let question = &response.questions[0];
-        println!("question: {} {:?}", question.name, question.q_type);
+        let opt_versions: Vec<_> = response
+            .additional_records
+            .iter()
+            .filter_map(|r| match r {
+                Record::Opt { version, .. } => Some(*version),
+                _ => None,
+            })
+            .collect();
+        response.additional_records.clear();
+        response.header.additional_count = 0;
+
+        let mut response = match opt_versions[..] {
+            [] => return self.answer(response),
+            [version] if version > EDNS_VERSION => {
+                // the upper bits of the BADVERS response code are carried by
+                // the OPT record
+                response.header.resp_code = ResponseCode::Success;
+                response.additional_records.push(opt_record(1));
+                response.header.additional_count = 1;
+                return response;
+            }
+            [_] => self.answer(response),
+            _ => {
+                response.header.resp_code = ResponseCode::FormatError;
+                response
+            }
+        };
+
+        response.additional_records.push(opt_record(0));
+        response.header.additional_count = response.additional_records.len() as u16;
+        response
+    }
+
+    /// Answers a DNS query from the closest zone that encloses the name in
+    /// the question, or refuses it if there is no such zone.
+    ///
+    /// CNAME records are followed within the served zones, and each record in
+    /// the chain is added to the answer section along with the records of the
+    /// final target. The response code is that of the final target (RFC 1034
+    /// section 4.3.2).
+    ///
+    /// Returns a DNS response.
+    fn answer(&self, query: Message) -> Message {
+        let mut response = query;
 
-        if !matches!(response.header.op_code, OperationCode::Query) {
-            response.header.resp_code = ResponseCode::NotImplemented;
-            println!("response: {:?}", response.header.resp_code);
+        if response.questions.len() != 1 {
+            response.header.resp_code = ResponseCode::FormatError;
             return response;
         }
 
-        let mut wildcard_answers: Option<Vec<&Record>> = None;
+        let question = &response.questions[0];
+        debug!("question: {} {:?}", question.name, question.q_type);
 
-        for qname in question.name.ancestors() {
-            let name_records = self.zone.find_with_name(&qname);
+        let q_type = question.q_type.clone();
+        let mut name = question.name.clone();
+        let mut aliases = vec![];
 
-            // if there are records at this level, discard wildcard answers
-            if !name_records.is_empty() {
-                wildcard_answers = None;
-            }
+        loop {
+            let Some(zone) = self.find_zone(&name) else {
+                // the client follows aliases to names outside the served
+                // zones itself
+                if aliases.is_empty() {
+                    response.header.resp_code = ResponseCode::Refused;
+                }
+                break;
+            };
 
-            // leaf
-            if qname == question.name {
-                // check for cname
-                if let Some(cname_record) = name_records
-                    .iter()
-                    .find(|r| matches!(r, Record::Cname { .. }))
-                {
+            match zone.lookup(&name, &q_type) {
+                Lookup::Answer(records) => {
                     response.header.is_authority = true;
                     response.header.resp_code = ResponseCode::Success;
-                    response.header.answer_count = 1;
-                    response.answer_records.push((*cname_record).clone());
-                    return response;
+                    response.answer_records.extend(records);
+                    break;
                 }
+                Lookup::Alias(records) => {
+                    let Some(Record::Cname { host: target, .. }) = records.last() else {
+                        unreachable!()
+                    };
+                    let target = target.clone();
 
-                // check for exact matches
-                let matched_records: Vec<_> = name_records
-                    .iter()
-                    .filter(|r| {
-                        r.code() == question.q_type.code()
-                            || matches!(question.q_type, QuestionType::ALL)
-                    })
-                    .collect();
-
-                if !matched_records.is_empty() {
                     response.header.is_authority = true;
                     response.header.resp_code = ResponseCode::Success;
-                    response.header.answer_count = matched_records.len() as u16;
-                    for record in matched_records {
-                        response.answer_records.push((*record).clone());
+                    for record in records {
+                        // a DNAME record may apply to several names in a chain
+                        if !response.answer_records.contains(&record) {
+                            response.answer_records.push(record);
+                        }
+                    }
+                    aliases.push(name);
+
+                    if aliases.contains(&target) {
+                        debug!("CNAME loop at {target}");
+                        break;
+                    }
+                    if aliases.len() >= MAX_CNAME_CHAIN {
+                        debug!("CNAME chain is longer than {MAX_CNAME_CHAIN} records");
+                        break;
                     }
-                    return response;
+
+                    name = target;
+                }
+                Lookup::NameExists(dname) => {
+                    response.header.is_authority = true;
+                    response.header.resp_code = ResponseCode::NameExists;
+                    response.answer_records.push(dname);
+                    break;
+                }
+                Lookup::Referral(records) => {
+                    // the answer is authoritative if it starts with an alias
+                    // in the zone
+                    response.header.is_authority = !response.answer_records.is_empty();
+                    response.header.resp_code = ResponseCode::Success;
+                    response.authority_records.extend(records);
+                    break;
+                }
+                Lookup::NoData => {
+                    response = self.negative(zone, response, ResponseCode::Success);
+                    break;
+                }
+                Lookup::NameError => {
+                    response = self.negative(zone, response, ResponseCode::NameError);
+                    break;
                 }
             }
+        }
 
-            // leaf or ancestor: check for delegation
-            let delegation_records: Vec<_> = name_records
-                .iter()
-                .filter(|r| matches!(r, Record::Ns { .. }))
-                .collect();
+        response.header.answer_count = response.answer_records.len() as u16;
+        response.header.authority_count = response.authority_records.len() as u16;
+        self.add_additional_records(&mut response);
+        response
+    }
 
-            if !delegation_records.is_empty() {
-                response.header.is_authority = false;
-                response.header.resp_code = ResponseCode::Success;
-                response.header.authority_count = delegation_records.len() as u16;
-                for record in delegation_records {
-                    response.authority_records.push((*record).clone());
-                }
-                return response;
+    /// Returns the closest zone that encloses a name, if any.
+    fn find_zone(&self, name: &Name) -> Option<&Zone> {
+        // ancestors are returned from the root down, so the last match is
+        // the closest
+        name.ancestors()
+            .filter_map(|ancestor| self.zones.iter().find(|z| z.name() == &ancestor))
+            .last()
+    }
+
+    /// Completes a negative response (NXDOMAIN or NODATA).
+    ///
+    /// The SOA record of the zone is added to the authority section so that
+    /// resolvers can cache the response, with its TTL capped to the minimum
+    /// field of the SOA record (RFC 2308).
+    fn negative(&self, zone: &Zone, mut response: Message, resp_code: ResponseCode) -> Message {
+        response.header.is_authority = true;
+        response.header.resp_code = resp_code;
+
+        if let Some(soa) = zone.soa() {
+            let mut soa = soa.clone();
+            if let Record::Soa { ttl, minimum, .. } = &mut soa {
+                *ttl = (*ttl).min(*minimum);
             }
+            response.authority_records.push(soa);
+            response.header.authority_count = response.authority_records.len() as u16;
+        }
 
-            // do not consider wildcards for root
-            if qname.is_root() {
-                continue;
+        response
+    }
+
+    /// Adds records that the client is likely to need to the additional
+    /// section of a response.
+    ///
+    /// For records that refer to hosts by name in the answer or authority
+    /// sections (e.g., NS, MX and SRV), these are the address records of the
+    /// hosts that are present in the served zones, which includes the glue
+    /// records of delegations. For SVCB and HTTPS answers, these are the
+    /// bindings that aliases refer to (following alias chains) and the
+    /// address records of service endpoints.
+    ///
+    /// Additional records that do not fit in the response are dropped when it
+    /// is encoded (see [`encode_within`]).
+    fn add_additional_records(&self, response: &mut Message) {
+        let mut additional_records = vec![];
+        let mut bindings = vec![];
+
+        for record in response
+            .answer_records
+            .iter()
+            .chain(&response.authority_records)
+        {
+            match record {
+                Record::Ns { host, .. }
+                | Record::Md { host, .. }
+                | Record::Mf { host, .. }
+                | Record::Mb { host, .. }
+                | Record::Mx { host, .. } => self.add_addresses(host, &mut additional_records),
+                Record::Srv { target, .. } => self.add_addresses(target, &mut additional_records),
+                Record::Svcb { .. } | Record::Https { .. } => bindings.push(record.clone()),
+                _ => {}
             }
+        }
+
+        while let Some(binding) = bindings.pop() {
+            let Some((owner, priority, target)) = service_binding(&binding) else {
+                continue;
+            };
 
-            // if there are records at this level, do not look for wildcard answers
-            if !name_records.is_empty() {
+            // an alias to the root name indicates that the service is not
+            // available
+            if priority == 0 && target.is_root() {
                 continue;
             }
 
-            // leaf or ancestor: check for wildcards
-            let wildcard_records: Vec<_> = self
-                .zone
-                .find_with_name(&qname.to_wildcard())
-                .into_iter()
-                .filter(|r| r.code() == question.q_type.code())
-                .collect();
+            let target = if target.is_root() { owner } else { target };
 
-            // if there are matching wildcard records, hang on to them
-            if !wildcard_records.is_empty() {
-                wildcard_answers = Some(wildcard_records);
+            if priority == 0 {
+                for record in self.find_with_name(target) {
+                    // bindings are only queued once, which prevents alias loops
+                    if record.code() == binding.code() && !additional_records.contains(record) {
+                        additional_records.push(record.clone());
+                        bindings.push(record.clone());
+                    }
+                }
             }
+
+            self.add_addresses(target, &mut additional_records);
         }
 
-        // there are matching wildcard records and no records for names in
-        // between the wildcard and the question name
-        if let Some(records) = wildcard_answers {
-            response.header.is_authority = true;
-            response.header.resp_code = ResponseCode::Success;
-            response.header.answer_count = records.len() as u16;
-            for record in records {
-                response
-                    .answer_records
-                    .push(record.with_name(question.name.clone()));
+        response.additional_records.extend(additional_records);
+        response.header.additional_count = response.additional_records.len() as u16;
+    }
+
+    /// Returns the records with a name in the closest zone that encloses it.
+    fn find_with_name(&self, name: &Name) -> Vec<&Record> {
+        self.find_zone(name)
+            .map(|zone| zone.find_with_name(name))
+            .unwrap_or_default()
+    }
+
+    /// Adds the address records for a name in the served zones to a list of
+    /// records.
+    fn add_addresses(&self, name: &Name, records: &mut Vec<Record>) {
+        for record in self.find_with_name(name) {
+            if matches!(record, Record::A { .. } | Record::Aaaa { .. }) && !records.contains(record)
+            {
+                records.push(record.clone());
             }
-            return response;
         }
+    }
+}
 
-        response.header.resp_code = ResponseCode::NameError;
-        response
+/// Largest UDP payload that the server accepts and sends.
+///
+/// This is advertised to clients in the OPT record of responses. The value
+/// avoids IP fragmentation on common networks.
+const MAX_UDP_PAYLOAD: u16 = 1232;
+
+/// Maximum number of queries that are served at once on each UDP socket.
+const MAX_UDP_IN_FLIGHT: usize = 1024;
+
+/// Maximum number of CNAME records that are followed for a query.
+const MAX_CNAME_CHAIN: usize = 8;
+
+/// Highest EDNS version that the server supports.
+const EDNS_VERSION: u8 = 0;
+
+/// Returns the largest UDP response that a client can accept.
+///
+/// Clients that do not use EDNS can accept 512 bytes. Larger sizes requested
+/// by clients are capped to the payload size of the server.
+fn udp_payload_size(query: &Message) -> usize {
+    let requested = query.additional_records.iter().find_map(|r| match r {
+        Record::Opt {
+            max_response_size, ..
+        } => Some(*max_response_size),
+        _ => None,
+    });
+    requested.unwrap_or(512).clamp(512, MAX_UDP_PAYLOAD) as usize
+}
+
+/// Creates the OPT record of a response.
+///
+/// The extended response code contains the upper eight bits of the response
+/// code.
+fn opt_record(extended_rcode: u8) -> Record {
+    Record::Opt {
+        name: Name::from_str(".").unwrap(),
+        max_response_size: MAX_UDP_PAYLOAD,
+        extended_rcode,
+        version: EDNS_VERSION,
+        dnssec_ok: false,
+        data: vec![],
+    }
+}
+
+/// Encodes a response so that it fits in a given number of bytes.
+///
+/// Additional records are dropped first, without setting the TC bit since
+/// they are optional. Glue records that a referral cannot be followed
+/// without are dropped next, and the TC bit is set (RFC 9471). If the
+/// response still does not fit, authority and answer records are dropped as
+/// well. The OPT record is always kept.
+///
+/// Glue records are placed before the other additional records, and the
+/// response is encoded once to find where its records end, and once more
+/// with the records that fit.
+fn encode_within(mut response: Message, max_size: usize) -> Vec<u8> {
+    // order the records by when they are dropped (with the OPT record last),
+    // so that the records that are kept precede some record offset in the
+    // encoded response
+    let (opt, mut additional): (Vec<_>, Vec<_>) = mem::take(&mut response.additional_records)
+        .into_iter()
+        .partition(|r| matches!(r, Record::Opt { .. }));
+    additional.sort_by_key(|r| !is_glue(&response, r));
+
+    let answers = response.answer_records.len();
+    let authorities = response.authority_records.len();
+    let glue = additional.iter().filter(|r| is_glue(&response, r)).count();
+    let droppable = answers + authorities + additional.len();
+    response.additional_records = additional;
+    response.additional_records.extend(opt);
+
+    let mut bytes = Bytes::new();
+    let offsets = response.to_bytes_with_offsets(&mut bytes);
+    let size = bytes.used().len();
+    if size <= max_size {
+        return bytes.used().to_vec();
+    }
+
+    // the size of the response with only the first records and the OPT
+    // record
+    let end = |records: usize| offsets.get(records).copied().unwrap_or(size);
+    let opt_size = size - end(droppable);
+    let kept = (0..droppable)
+        .rev()
+        .find(|&records| end(records) + opt_size <= max_size)
+        .unwrap_or(0);
+
+    if kept < answers + authorities + glue {
+        response.header.is_truncated = true;
+    }
+    response.answer_records.truncate(kept);
+    response
+        .authority_records
+        .truncate(kept.saturating_sub(answers));
+    response
+        .additional_records
+        .drain(kept.saturating_sub(answers + authorities)..droppable - answers - authorities);
+
+    response.header.answer_count = response.answer_records.len() as u16;
+    response.header.authority_count = response.authority_records.len() as u16;
+    response.header.additional_count = response.additional_records.len() as u16;
+
+    let mut bytes = Bytes::new();
+    response.to_bytes(&mut bytes);
+    bytes.used().to_vec()
+}
+
+/// Returns true if a record is a glue record for a referral in a response.
+///
+/// Glue records are the addresses of name servers that are within the
+/// delegated zone, which cannot be resolved without them.
+fn is_glue(response: &Message, record: &Record) -> bool {
+    if response.header.is_authority || !matches!(record, Record::A { .. } | Record::Aaaa { .. }) {
+        return false;
+    }
+
+    response.authority_records.iter().any(|r| match r {
+        Record::Ns { name, host, .. } => host == record.name() && host.is_subdomain_of(name),
+        _ => false,
+    })
+}
+
+/// Limits on the TCP connections of a server.
+#[derive(Clone)]
+struct TcpLimits {
+    /// Time after which an idle connection is closed.
+    idle_timeout: Duration,
+    /// Permits for concurrent connections, of which there are as many as
+    /// the maximum number of connections.
+    connections: Arc<Semaphore>,
+}
+
+/// Runs an I/O operation, failing with a TimedOut error if it does not
+/// complete in time.
+async fn with_timeout<T>(
+    duration: Duration,
+    operation: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    timeout(duration, operation)
+        .await
+        .unwrap_or_else(|_| Err(ErrorKind::TimedOut.into()))
+}
+
+/// Whether SO_REUSEPORT is available, so that each UDP worker can have a
+/// socket of its own.
+const HAS_REUSE_PORT: bool = cfg!(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos"))
+));
+
+/// Binds UDP sockets to an address, one for each worker.
+///
+/// With more than one worker, the sockets are bound with SO_REUSEPORT, so
+/// that the operating system spreads queries across them. Where that is not
+/// available, the workers share a single socket.
+fn bind_udp(addr: SocketAddr, workers: usize) -> io::Result<Vec<std::net::UdpSocket>> {
+    let reuse_port = workers > 1 && HAS_REUSE_PORT;
+    let first = bind_udp_socket(addr, reuse_port)?;
+    // the other sockets must be bound to the same port if it was chosen by
+    // the operating system
+    let addr = first.local_addr()?;
+
+    let mut sockets = vec![first];
+    while sockets.len() < workers {
+        let socket = if reuse_port {
+            bind_udp_socket(addr, true)?
+        } else {
+            sockets[0].try_clone()?
+        };
+        sockets.push(socket);
+    }
+    Ok(sockets)
+}
+
+/// Binds a UDP socket to an address.
+///
+/// IPv6 sockets only accept IPv6 traffic, so that the same port can be bound
+/// separately for IPv4.
+fn bind_udp_socket(addr: SocketAddr, reuse_port: bool) -> io::Result<std::net::UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    socket.set_reuse_port(reuse_port)?;
+    #[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+    let _ = reuse_port;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    Ok(socket.into())
+}
+
+/// Binds a TCP listener to an address.
+///
+/// As with UDP, IPv6 listeners only accept IPv6 connections.
+fn bind_tcp(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(128)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Returns the owner, priority and target of a service binding record.
+///
+/// Returns None if the record is not a service binding.
+fn service_binding(record: &Record) -> Option<(&Name, u16, &Name)> {
+    match record {
+        Record::Svcb {
+            name,
+            priority,
+            target,
+            ..
+        }
+        | Record::Https {
+            name,
+            priority,
+            target,
+            ..
+        } => Some((name, *priority, target)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, str::FromStr, sync::Arc, thread, time::Duration};
+
+    use dex::{
+        Bytes, Message, Name, OperationCode, Question, QuestionClass, QuestionType, Record,
+        ResponseCode, Zone,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream, UdpSocket},
+        sync::{watch, Semaphore},
+        time::timeout,
+    };
+
+    use crate::{
+        bind_tcp, bind_udp, encode_within, opt_record, udp_payload_size, Server, TcpLimits,
+        HAS_REUSE_PORT,
+    };
+
+    /// Creates a query for a single question.
+    fn query(name: &str, q_type: QuestionType) -> Message {
+        let mut query = Message::new();
+        query.header.question_count = 1;
+        query.questions = vec![Question {
+            name: Name::from_str(name).unwrap(),
+            q_type,
+            q_class: QuestionClass::In,
+        }];
+        query
+    }
+
+    /// Encodes a message with the length prefix used over TCP.
+    fn tcp_message(message: &Message) -> Vec<u8> {
+        let mut bytes = Bytes::new();
+        message.to_bytes(&mut bytes);
+        let mut buf = (bytes.used().len() as u16).to_be_bytes().to_vec();
+        buf.extend_from_slice(bytes.used());
+        buf
+    }
+
+    /// Reads a message with a length prefix from a TCP stream.
+    async fn read_tcp_message(stream: &mut TcpStream) -> Message {
+        let len = stream.read_u16().await.unwrap();
+        let mut buf = vec![0; len as usize];
+        stream.read_exact(&mut buf).await.unwrap();
+        Message::from_bytes(&mut Bytes::from_buf(&buf)).unwrap()
+    }
+
+    #[test]
+    fn format_error_for_malformed_query() {
+        // header with one question, followed by a truncated name
+        let query = [0, 7, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 3, b'c'];
+        let response = Server::error_response(&query, ResponseCode::FormatError).unwrap();
+        assert_eq!(response.header.id, 7);
+        assert!(response.header.is_response);
+        assert!(matches!(
+            response.header.resp_code,
+            ResponseCode::FormatError
+        ));
+    }
+
+    #[test]
+    fn not_implemented_for_update() {
+        let server = Server { zones: vec![] };
+
+        // an UPDATE (opcode 5) with a zone section and an update record of
+        // class NONE, which does not decode like a query
+        let mut query = vec![0, 9, 5 << 3, 0, 0, 1, 0, 0, 0, 1, 0, 0];
+        query.extend([0, 0, 6, 0, 1]);
+        query.extend([0, 0, 1, 0, 254, 0, 0, 0, 0, 0, 0]);
+        let addr = SocketAddr::from(([127, 0, 0, 1], 5300));
+        let response = server.handle(&query, addr, true).unwrap();
+
+        let response = Message::from_bytes(&mut Bytes::from_buf(&response)).unwrap();
+        assert_eq!(response.header.id, 9);
+        assert!(response.header.is_response);
+        assert_eq!(response.header.op_code, OperationCode::Unknown(5));
+        assert_eq!(response.header.resp_code, ResponseCode::NotImplemented);
+    }
+
+    #[test]
+    fn responses_are_dropped() {
+        let zone = Zone::from_master(
+            "@ 60 IN A 192.0.2.1\n",
+            Some(Name::from_str("example.com.").unwrap()),
+        )
+        .unwrap();
+        let server = Server { zones: vec![zone] };
+
+        let mut message = query("example.com.", QuestionType::A);
+        message.header.is_response = true;
+        let mut bytes = Bytes::new();
+        message.to_bytes(&mut bytes);
+        let addr = SocketAddr::from(([127, 0, 0, 1], 5300));
+        assert!(server.handle(bytes.used(), addr, true).is_none());
+        assert!(server.handle(bytes.used(), addr, false).is_none());
+    }
+
+    #[test]
+    fn srv_answer_includes_target_addresses() {
+        let zone = Zone::from_toml(
+            r#"
+            name = "example.com."
+
+            [[records]]
+            name = "_sip._tcp.example.com."
+            class = "IN"
+            ttl = 60
+            type = "SRV"
+            priority = 10
+            weight = 5
+            port = 5060
+            target = "sip.example.com."
+
+            [[records]]
+            name = "sip.example.com."
+            class = "IN"
+            ttl = 60
+            type = "A"
+            addr = "192.0.2.1"
+            "#,
+        )
+        .unwrap();
+        let server = Server { zones: vec![zone] };
+
+        let response = server.serve(query("_sip._tcp.example.com.", QuestionType::SRV));
+        assert_eq!(response.answer_records.len(), 1);
+        assert_eq!(response.header.additional_count, 1);
+        assert_eq!(
+            response.additional_records[0].to_string(),
+            "sip.example.com. IN 60 A 192.0.2.1"
+        );
+    }
+
+    #[test]
+    fn https_alias_is_followed() {
+        let zone = Zone::from_toml(
+            r#"
+            name = "example.com."
+
+            [[records]]
+            name = "example.com."
+            class = "IN"
+            ttl = 60
+            type = "HTTPS"
+            priority = 0
+            target = "svc.example.com."
+
+            [[records]]
+            name = "example.com."
+            class = "IN"
+            ttl = 60
+            type = "HTTPS"
+            priority = 1
+            target = "."
+
+            [[records]]
+            name = "svc.example.com."
+            class = "IN"
+            ttl = 60
+            type = "HTTPS"
+            priority = 1
+            target = "."
+            params = ["alpn=h2,h3", "port=8443"]
+
+            [[records]]
+            name = "svc.example.com."
+            class = "IN"
+            ttl = 60
+            type = "AAAA"
+            addr = "2001:db8::1"
+            "#,
+        )
+        .unwrap();
+        let server = Server { zones: vec![zone] };
+
+        let response = server.serve(query("example.com.", QuestionType::HTTPS));
+        assert_eq!(response.answer_records.len(), 1);
+        assert_eq!(
+            response.answer_records[0].to_string(),
+            "example.com. IN 60 HTTPS 0 svc.example.com."
+        );
+        let additional: Vec<_> = response
+            .additional_records
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        assert_eq!(
+            additional,
+            [
+                "svc.example.com. IN 60 HTTPS 1 . alpn=h2,h3 port=8443",
+                "svc.example.com. IN 60 AAAA 2001:db8::1"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn tcp_queries_are_pipelined() {
+        let zone = Zone::from_master(
+            "@ 60 IN A 192.0.2.1\n@ 60 IN AAAA 2001:db8::1\n",
+            Some(Name::from_str("example.com.").unwrap()),
+        )
+        .unwrap();
+        let server = Server { zones: vec![zone] };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let (stream, peer) = listener.accept().await.unwrap();
+
+        let client = async {
+            // send both queries before reading any response
+            let mut requests = vec![];
+            for (id, q_type) in [(1, QuestionType::A), (2, QuestionType::AAAA)] {
+                let mut request = query("example.com.", q_type);
+                request.header.id = id;
+                requests.extend_from_slice(&tcp_message(&request));
+            }
+            client.write_all(&requests).await.unwrap();
+
+            for id in [1, 2] {
+                let response = read_tcp_message(&mut client).await;
+                assert_eq!(response.header.id, id);
+                assert_eq!(response.answer_records.len(), 1);
+            }
+
+            client.shutdown().await.unwrap();
+        };
+
+        let (served, ()) = tokio::join!(
+            server.serve_tcp(stream, peer, Duration::from_secs(5)),
+            client
+        );
+        served.unwrap();
+    }
+
+    #[test]
+    fn udp_workers_share_address() {
+        let zone = Zone::from_master(
+            "@ 60 IN A 192.0.2.1\n",
+            Some(Name::from_str("example.com.").unwrap()),
+        )
+        .unwrap();
+        let server = Arc::new(Server { zones: vec![zone] });
+
+        let sockets = bind_udp(SocketAddr::from(([127, 0, 0, 1], 0)), 4).unwrap();
+        let addr = sockets[0].local_addr().unwrap();
+        assert_eq!(sockets.len(), 4);
+        assert!(sockets.iter().all(|s| s.local_addr().unwrap() == addr));
+
+        let mut bytes = Bytes::new();
+        query("example.com.", QuestionType::A).to_bytes(&mut bytes);
+        let clients: Vec<_> = (0..32)
+            .map(|_| {
+                let client = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+                client
+                    .set_read_timeout(Some(Duration::from_secs(1)))
+                    .unwrap();
+                client
+            })
+            .collect();
+
+        // queries from different source ports are spread across the sockets
+        for client in &clients {
+            client.send_to(bytes.used(), addr).unwrap();
+        }
+        let received: Vec<_> = sockets
+            .iter()
+            .map(|socket| {
+                let mut buf = [0; 512];
+                std::iter::from_fn(|| socket.recv_from(&mut buf).ok()).count()
+            })
+            .collect();
+        assert_eq!(received.iter().sum::<usize>(), 32);
+        if HAS_REUSE_PORT {
+            assert!(received.iter().filter(|&&count| count > 0).count() > 1);
+        }
+
+        let (stop, stopped) = watch::channel(());
+        let workers: Vec<_> = sockets
+            .into_iter()
+            .map(|socket| {
+                let server = server.clone();
+                let mut stopped = stopped.clone();
+                let shutdown = async move {
+                    let _ = stopped.changed().await;
+                };
+                thread::spawn(move || server.serve_udp_worker(socket, shutdown))
+            })
+            .collect();
+
+        for client in &clients {
+            client.send_to(bytes.used(), addr).unwrap();
+            let mut buf = [0; 512];
+            let len = client.recv(&mut buf).unwrap();
+            let response = Message::from_bytes(&mut Bytes::from_buf(&buf[..len])).unwrap();
+            assert_eq!(response.answer_records.len(), 1);
+        }
+
+        drop(stop);
+        for worker in workers {
+            worker.join().unwrap().unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn udp_queries_are_served_concurrently() {
+        let zone = Zone::from_master(
+            "@ 60 IN A 192.0.2.1\n",
+            Some(Name::from_str("example.com.").unwrap()),
+        )
+        .unwrap();
+        let server = Arc::new(Server { zones: vec![zone] });
+
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let socket = UdpSocket::from_std(bind_udp(addr, 1).unwrap().remove(0)).unwrap();
+        let server_addr = socket.local_addr().unwrap();
+        let serving = tokio::spawn(server.serve_udp(socket));
+
+        // send all queries before reading any response
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for id in 0..64 {
+            let mut request = query("example.com.", QuestionType::A);
+            request.header.id = id;
+            let mut bytes = Bytes::new();
+            request.to_bytes(&mut bytes);
+            client.send_to(bytes.used(), server_addr).await.unwrap();
+        }
+
+        let mut ids = vec![];
+        let mut buf = [0; 512];
+        for _ in 0..64 {
+            let len = timeout(Duration::from_secs(1), client.recv(&mut buf));
+            let len = len.await.unwrap().unwrap();
+            let response = Message::from_bytes(&mut Bytes::from_buf(&buf[..len])).unwrap();
+            assert_eq!(response.answer_records.len(), 1);
+            ids.push(response.header.id);
+        }
+        ids.sort();
+        assert_eq!(ids, (0..64).collect::<Vec<_>>());
+        serving.abort();
+    }
+
+    #[tokio::test]
+    async fn slow_tcp_client_does_not_block_queries() {
+        let zone = Zone::from_master(
+            "@ 60 IN A 192.0.2.1\n",
+            Some(Name::from_str("example.com.").unwrap()),
+        )
+        .unwrap();
+        let server = Arc::new(Server { zones: vec![zone] });
+
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let socket = UdpSocket::from_std(bind_udp(addr, 1).unwrap().remove(0)).unwrap();
+        let listener = bind_tcp(addr).unwrap();
+        let udp_addr = socket.local_addr().unwrap();
+        let tcp_addr = listener.local_addr().unwrap();
+        let limits = TcpLimits {
+            idle_timeout: Duration::from_secs(5),
+            connections: Arc::new(Semaphore::new(10)),
+        };
+        tokio::spawn(server.clone().serve_udp(socket));
+        tokio::spawn(server.accept_tcp(listener, limits));
+
+        // a client that sends part of a query and then stalls
+        let mut slow_client = TcpStream::connect(tcp_addr).await.unwrap();
+        slow_client.write_all(&[0]).await.unwrap();
+
+        let request = query("example.com.", QuestionType::A);
+        let mut client = TcpStream::connect(tcp_addr).await.unwrap();
+        client.write_all(&tcp_message(&request)).await.unwrap();
+        let response = timeout(Duration::from_secs(1), read_tcp_message(&mut client));
+        assert_eq!(response.await.unwrap().answer_records.len(), 1);
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut bytes = Bytes::new();
+        request.to_bytes(&mut bytes);
+        client.send_to(bytes.used(), udp_addr).await.unwrap();
+        let mut buf = [0; 512];
+        let len = timeout(Duration::from_secs(1), client.recv(&mut buf));
+        let len = len.await.unwrap().unwrap();
+        let response = Message::from_bytes(&mut Bytes::from_buf(&buf[..len])).unwrap();
+        assert_eq!(response.answer_records.len(), 1);
+    }
+
+    #[test]
+    fn large_udp_response_is_truncated() {
+        let records: String = (0..12)
+            .map(|i| format!("@ 60 IN TXT \"{}\"\n", "x".repeat(50 + i)))
+            .collect();
+        let zone = Zone::from_master(&records, Some(Name::from_str("example.com.").unwrap()));
+        let server = Server {
+            zones: vec![zone.unwrap()],
+        };
+
+        // without EDNS, responses are limited to 512 bytes
+        let request = query("example.com.", QuestionType::TXT);
+        assert_eq!(udp_payload_size(&request), 512);
+        let response = encode_within(server.serve(request), 512);
+        let response = Message::from_bytes(&mut Bytes::from_buf(&response)).unwrap();
+        assert!(response.header.is_truncated);
+        assert!(response.answer_records.len() < 12);
+
+        // with EDNS, the client's buffer size is used up to the server's limit
+        let mut request = query("example.com.", QuestionType::TXT);
+        request.additional_records.push(opt_record(0));
+        let max_size = udp_payload_size(&request);
+        assert_eq!(max_size, 1232);
+        let response = encode_within(server.serve(request), max_size);
+        let response = Message::from_bytes(&mut Bytes::from_buf(&response)).unwrap();
+        assert!(!response.header.is_truncated);
+        assert_eq!(response.answer_records.len(), 12);
+        assert!(matches!(
+            response.additional_records[..],
+            [Record::Opt { version: 0, .. }]
+        ));
+
+        // only the answers that do not fit are dropped, and the OPT record
+        // is kept
+        let mut request = query("example.com.", QuestionType::TXT);
+        request.additional_records.push(opt_record(0));
+        let full = server.serve(request);
+        let encoded = encode_within(full.clone(), 600);
+        assert!(encoded.len() <= 600);
+        let response = Message::from_bytes(&mut Bytes::from_buf(&encoded)).unwrap();
+        assert!(response.header.is_truncated);
+        assert!(matches!(
+            response.additional_records[..],
+            [Record::Opt { .. }]
+        ));
+
+        let answers = response.answer_records.len();
+        let mut larger = response;
+        larger.answer_records = full.answer_records[..answers + 1].to_vec();
+        let mut bytes = Bytes::new();
+        larger.to_bytes(&mut bytes);
+        assert!(bytes.used().len() > 600);
+    }
+
+    #[test]
+    fn unsupported_edns_version_is_rejected() {
+        let zone = Zone::from_master("@ 60 IN A 192.0.2.1", Some(Name::from_str(".").unwrap()));
+        let server = Server {
+            zones: vec![zone.unwrap()],
+        };
+
+        let mut request = query(".", QuestionType::A);
+        let mut opt = opt_record(0);
+        if let Record::Opt { version, .. } = &mut opt {
+            *version = 1;
+        }
+        request.additional_records.push(opt);
+        request.header.additional_count = 1;
+
+        let response = server.serve(request);
+        assert!(response.answer_records.is_empty());
+        assert!(matches!(
+            response.additional_records[..],
+            [Record::Opt {
+                extended_rcode: 1,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn negative_responses_include_soa() {
+        let zone = Zone::from_master(
+            "$ORIGIN example.com.\n\
+             @ 3600 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             www 60 IN A 192.0.2.1\n\
+             a.b 60 IN A 192.0.2.2\n",
+            None,
+        );
+        let server = Server {
+            zones: vec![zone.unwrap()],
+        };
+        let soa =
+            "example.com. IN 300 SOA ns.example.com. admin.example.com. 1 7200 3600 1209600 300";
+
+        for (name, resp_code) in [
+            ("missing.example.com.", ResponseCode::NameError),
+            ("www.example.com.", ResponseCode::Success),
+            // empty non-terminal
+            ("b.example.com.", ResponseCode::Success),
+        ] {
+            let response = server.serve(query(name, QuestionType::AAAA));
+            assert!(response.header.is_authority);
+            assert_eq!(response.header.resp_code, resp_code);
+            assert!(response.answer_records.is_empty());
+            assert_eq!(response.header.authority_count, 1);
+            assert_eq!(response.authority_records[0].to_string(), soa);
+        }
+    }
+
+    #[test]
+    fn referral_includes_glue() {
+        let zone = Zone::from_master(
+            "$ORIGIN example.com.\n\
+             @ 60 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             @ 60 IN NS ns\n\
+             ns 60 IN A 192.0.2.1\n\
+             sub 60 IN NS ns.sub\n\
+             sub 60 IN NS ns.example.net.\n\
+             ns.sub 60 IN A 192.0.2.2\n",
+            None,
+        );
+        let server = Server {
+            zones: vec![zone.unwrap()],
+        };
+
+        let response = server.serve(query("www.sub.example.com.", QuestionType::A));
+        assert!(!response.header.is_authority);
+        assert_eq!(response.authority_records.len(), 2);
+        assert_eq!(response.header.additional_count, 1);
+        assert_eq!(
+            response.additional_records[0].to_string(),
+            "ns.sub.example.com. IN 60 A 192.0.2.2"
+        );
+
+        // glue that does not fit truncates the response
+        let mut bytes = Bytes::new();
+        response.to_bytes(&mut bytes);
+        let response = encode_within(response, bytes.used().len() - 1);
+        let response = Message::from_bytes(&mut Bytes::from_buf(&response)).unwrap();
+        assert!(response.header.is_truncated);
+        assert!(response.additional_records.is_empty());
+
+        // queries for the zone cut itself and for the glue are referred too
+        for (name, q_type) in [
+            ("sub.example.com.", QuestionType::NS),
+            ("ns.sub.example.com.", QuestionType::A),
+        ] {
+            let response = server.serve(query(name, q_type));
+            assert!(!response.header.is_authority);
+            assert!(response.answer_records.is_empty());
+            assert_eq!(response.authority_records.len(), 2);
+        }
+
+        // name servers at the apex are answers, not referrals
+        let response = server.serve(query("example.com.", QuestionType::NS));
+        assert!(response.header.is_authority);
+        assert_eq!(
+            response.additional_records[0].to_string(),
+            "ns.example.com. IN 60 A 192.0.2.1"
+        );
+    }
+
+    #[test]
+    fn cname_chains_are_followed() {
+        let zone = Zone::from_master(
+            "$ORIGIN example.com.\n\
+             @ 60 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             www 60 IN CNAME web\n\
+             web 60 IN CNAME host\n\
+             host 60 IN A 192.0.2.1\n\
+             dangling 60 IN CNAME missing\n\
+             loop1 60 IN CNAME loop2\n\
+             loop2 60 IN CNAME loop1\n\
+             external 60 IN CNAME www.example.net.\n",
+            None,
+        );
+        let server = Server {
+            zones: vec![zone.unwrap()],
+        };
+
+        let answers = |name: &str| {
+            let response = server.serve(query(name, QuestionType::A));
+            let answers: Vec<_> = response
+                .answer_records
+                .iter()
+                .map(|r| r.to_string())
+                .collect();
+            (response.header.resp_code, answers)
+        };
+
+        assert_eq!(
+            answers("www.example.com."),
+            (
+                ResponseCode::Success,
+                vec![
+                    "www.example.com. IN 60 CNAME web.example.com.".to_owned(),
+                    "web.example.com. IN 60 CNAME host.example.com.".to_owned(),
+                    "host.example.com. IN 60 A 192.0.2.1".to_owned(),
+                ]
+            )
+        );
+
+        let (resp_code, records) = answers("dangling.example.com.");
+        assert_eq!(resp_code, ResponseCode::NameError);
+        assert_eq!(records.len(), 1);
+
+        let (resp_code, records) = answers("loop1.example.com.");
+        assert_eq!(resp_code, ResponseCode::Success);
+        assert_eq!(records.len(), 2);
+
+        let (resp_code, records) = answers("external.example.com.");
+        assert_eq!(resp_code, ResponseCode::Success);
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn dname_synthesizes_cname() {
+        let long_target = format!("{}.", vec!["a".repeat(50); 4].join("."));
+        let zone = Zone::from_master(
+            &format!(
+                "$ORIGIN example.com.\n\
+                 @ 60 IN SOA ns admin 1 7200 3600 1209600 300\n\
+                 old 60 IN DNAME new\n\
+                 www.new 60 IN A 192.0.2.1\n\
+                 long 60 IN DNAME {long_target}\n"
+            ),
+            None,
+        );
+        let server = Server {
+            zones: vec![zone.unwrap()],
+        };
+
+        let response = server.serve(query("www.old.example.com.", QuestionType::A));
+        let answers: Vec<_> = response
+            .answer_records
+            .iter()
+            .map(|r| r.to_string())
+            .collect();
+        assert_eq!(response.header.resp_code, ResponseCode::Success);
+        assert_eq!(
+            answers,
+            vec![
+                "old.example.com. IN 60 DNAME new.example.com.".to_owned(),
+                "www.old.example.com. IN 60 CNAME www.new.example.com.".to_owned(),
+                "www.new.example.com. IN 60 A 192.0.2.1".to_owned(),
+            ]
+        );
+
+        let name = format!("{}.long.example.com.", "b".repeat(60));
+        let response = server.serve(query(&name, QuestionType::A));
+        assert_eq!(response.header.resp_code, ResponseCode::NameExists);
+        assert_eq!(response.answer_records.len(), 1);
+    }
+
+    #[test]
+    fn closest_zone_is_selected() {
+        let parent = Zone::from_master(
+            "$ORIGIN example.com.\n\
+             @ 60 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             sub 60 IN NS ns.sub\n\
+             ns.sub 60 IN A 192.0.2.53\n\
+             alias 60 IN CNAME www.example.net.\n",
+            None,
+        );
+        let child = Zone::from_master(
+            "$ORIGIN sub.example.com.\n\
+             @ 60 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             www 60 IN A 192.0.2.2\n",
+            None,
+        );
+        let other = Zone::from_master(
+            "$ORIGIN example.net.\n\
+             @ 60 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             www 60 IN A 192.0.2.3\n",
+            None,
+        );
+        let server = Server {
+            zones: vec![parent.unwrap(), child.unwrap(), other.unwrap()],
+        };
+
+        // the child zone is used instead of the delegation in the parent
+        let response = server.serve(query("www.sub.example.com.", QuestionType::A));
+        assert!(response.header.is_authority);
+        assert_eq!(response.answer_records.len(), 1);
+
+        let response = server.serve(query("missing.sub.example.com.", QuestionType::A));
+        assert_eq!(response.header.resp_code, ResponseCode::NameError);
+        assert_eq!(
+            response.authority_records[0].name(),
+            &Name::from_str("sub.example.com.").unwrap()
+        );
+
+        // aliases are followed into other served zones
+        let response = server.serve(query("alias.example.com.", QuestionType::A));
+        assert_eq!(response.answer_records.len(), 2);
+
+        let response = server.serve(query("www.example.org.", QuestionType::A));
+        assert_eq!(response.header.resp_code, ResponseCode::Refused);
+        assert!(!response.header.is_authority);
+        assert!(response.answer_records.is_empty());
+    }
+
+    #[test]
+    fn names_are_matched_case_insensitively() {
+        let zone = Zone::from_master(
+            "$ORIGIN example.com.\n\
+             @ 60 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             www 60 IN A 192.0.2.1\n",
+            None,
+        );
+        let server = Server {
+            zones: vec![zone.unwrap()],
+        };
+
+        let response = server.serve(query("WWW.Example.COM.", QuestionType::A));
+        assert_eq!(response.header.resp_code, ResponseCode::Success);
+        assert_eq!(
+            response.answer_records[0].to_string(),
+            "WWW.Example.COM. IN 60 A 192.0.2.1"
+        );
     }
 }
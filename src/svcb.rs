@@ -0,0 +1,375 @@
+use std::{
+    fmt::Display,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{de::Visitor, Deserialize, Serialize};
+
+use crate::{split_unescaped, unescape, Bytes, DecodeError};
+
+/// A service binding parameter (RFC 9460).
+///
+/// Parameters appear in the record data of SVCB and HTTPS records. In
+/// presentation format, a parameter is written as `key=value` (e.g.,
+/// `alpn=h2,h3`), or as a bare key for parameters without a value.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SvcParam {
+    /// Keys that must be understood by the client to use the binding.
+    Mandatory(Vec<u16>),
+    /// Supported application protocols.
+    Alpn(Vec<String>),
+    /// Whether the default application protocol is unsupported.
+    NoDefaultAlpn,
+    /// Alternative port for the service.
+    Port(u16),
+    /// IPv4 addresses of the service.
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// Encrypted client hello configuration.
+    Ech(Vec<u8>),
+    /// IPv6 addresses of the service.
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// Parameter without dedicated support.
+    Unknown { key: u16, value: Vec<u8> },
+}
+
+impl SvcParam {
+    /// Returns the key of the parameter.
+    pub fn key(&self) -> u16 {
+        match self {
+            SvcParam::Mandatory(_) => 0,
+            SvcParam::Alpn(_) => 1,
+            SvcParam::NoDefaultAlpn => 2,
+            SvcParam::Port(_) => 3,
+            SvcParam::Ipv4Hint(_) => 4,
+            SvcParam::Ech(_) => 5,
+            SvcParam::Ipv6Hint(_) => 6,
+            SvcParam::Unknown { key, .. } => *key,
+        }
+    }
+
+    /// Creates a SvcParam from a byte stream.
+    pub(crate) fn from_bytes(bytes: &mut Bytes) -> Result<Self, DecodeError> {
+        let offset = bytes.pos();
+        let key = bytes.read_u16()?;
+        let len = bytes.read_u16()? as usize;
+        let value = bytes.read_exact(len)?;
+        let bad_param = DecodeError::BadSvcParam { offset };
+
+        let param = match key {
+            0 if len.is_multiple_of(2) => SvcParam::Mandatory(
+                value
+                    .chunks(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect(),
+            ),
+            1 => {
+                let mut ids = vec![];
+                let mut value = &value[..];
+                while let Some((&len, rest)) = value.split_first() {
+                    if len == 0 || rest.len() < len as usize {
+                        return Err(bad_param);
+                    }
+                    let (id, rest) = rest.split_at(len as usize);
+                    ids.push(String::from_utf8(id.to_vec()).map_err(|_| bad_param.clone())?);
+                    value = rest;
+                }
+                SvcParam::Alpn(ids)
+            }
+            2 if len == 0 => SvcParam::NoDefaultAlpn,
+            3 if len == 2 => SvcParam::Port(u16::from_be_bytes([value[0], value[1]])),
+            4 if len.is_multiple_of(4) => SvcParam::Ipv4Hint(
+                value
+                    .chunks(4)
+                    .map(|c| Ipv4Addr::new(c[0], c[1], c[2], c[3]))
+                    .collect(),
+            ),
+            5 => SvcParam::Ech(value),
+            6 if len.is_multiple_of(16) => SvcParam::Ipv6Hint(
+                value
+                    .chunks(16)
+                    .map(|c| Ipv6Addr::from(<[u8; 16]>::try_from(c).unwrap()))
+                    .collect(),
+            ),
+            0..=6 => return Err(bad_param),
+            _ => SvcParam::Unknown { key, value },
+        };
+
+        Ok(param)
+    }
+
+    /// Converts a SvcParam to a byte stream.
+    pub(crate) fn to_bytes(&self, bytes: &mut Bytes) {
+        bytes.write_u16(self.key());
+
+        let pos = bytes.pos();
+        bytes.write_u16(0);
+
+        match self {
+            SvcParam::Mandatory(keys) => {
+                for key in keys {
+                    bytes.write_u16(*key);
+                }
+            }
+            SvcParam::Alpn(ids) => {
+                for id in ids {
+                    bytes.write(id.len() as u8);
+                    bytes.write_all(id.as_bytes());
+                }
+            }
+            SvcParam::NoDefaultAlpn => {}
+            SvcParam::Port(port) => bytes.write_u16(*port),
+            SvcParam::Ipv4Hint(addrs) => {
+                for addr in addrs {
+                    bytes.write_all(&addr.octets());
+                }
+            }
+            SvcParam::Ech(config) => bytes.write_all(config),
+            SvcParam::Ipv6Hint(addrs) => {
+                for addr in addrs {
+                    bytes.write_all(&addr.octets());
+                }
+            }
+            SvcParam::Unknown { value, .. } => bytes.write_all(value),
+        }
+
+        let size = bytes.pos() - (pos + 2);
+        bytes.set_u16(pos, size as u16);
+    }
+}
+
+/// Returns the presentation name of a parameter key.
+fn key_name(key: u16) -> String {
+    match key {
+        0 => "mandatory".to_owned(),
+        1 => "alpn".to_owned(),
+        2 => "no-default-alpn".to_owned(),
+        3 => "port".to_owned(),
+        4 => "ipv4hint".to_owned(),
+        5 => "ech".to_owned(),
+        6 => "ipv6hint".to_owned(),
+        _ => format!("key{key}"),
+    }
+}
+
+/// Parses the presentation name of a parameter key.
+fn key_from_name(name: &str) -> anyhow::Result<u16> {
+    let key = match name {
+        "mandatory" => 0,
+        "alpn" => 1,
+        "no-default-alpn" => 2,
+        "port" => 3,
+        "ipv4hint" => 4,
+        "ech" => 5,
+        "ipv6hint" => 6,
+        _ => match name.strip_prefix("key").map(u16::from_str) {
+            Some(Ok(key)) => key,
+            _ => bail!("unsupported service parameter key: {name}"),
+        },
+    };
+    Ok(key)
+}
+
+/// Escapes a value for presentation.
+///
+/// Commas are escaped as well so that the value can appear in a list.
+fn escape(value: &[u8]) -> String {
+    let mut text = String::new();
+    for byte in value {
+        match byte {
+            b',' | b'\\' | b'"' => {
+                text.push('\\');
+                text.push(*byte as char);
+            }
+            b'!'..=b'~' => text.push(*byte as char),
+            _ => text.push_str(&format!("\\{byte:03}")),
+        }
+    }
+    text
+}
+
+impl Display for SvcParam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = key_name(self.key());
+
+        let join = |items: Vec<String>| items.join(",");
+
+        match self {
+            SvcParam::Mandatory(keys) => {
+                let keys = keys.iter().map(|k| key_name(*k)).collect();
+                write!(f, "{name}={}", join(keys))
+            }
+            SvcParam::Alpn(ids) => {
+                let ids = ids.iter().map(|id| escape(id.as_bytes())).collect();
+                write!(f, "{name}={}", join(ids))
+            }
+            SvcParam::NoDefaultAlpn => write!(f, "{name}"),
+            SvcParam::Port(port) => write!(f, "{name}={port}"),
+            SvcParam::Ipv4Hint(addrs) => {
+                let addrs = addrs.iter().map(|a| a.to_string()).collect();
+                write!(f, "{name}={}", join(addrs))
+            }
+            SvcParam::Ech(config) => write!(f, "{name}={}", BASE64.encode(config)),
+            SvcParam::Ipv6Hint(addrs) => {
+                let addrs = addrs.iter().map(|a| a.to_string()).collect();
+                write!(f, "{name}={}", join(addrs))
+            }
+            SvcParam::Unknown { value, .. } if value.is_empty() => write!(f, "{name}"),
+            SvcParam::Unknown { value, .. } => write!(f, "{name}={}", escape(value)),
+        }
+    }
+}
+
+impl FromStr for SvcParam {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => {
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                (name, Some(value))
+            }
+            None => (s, None),
+        };
+
+        let key = key_from_name(name)?;
+
+        let param = match (key, value) {
+            (0, Some(value)) => SvcParam::Mandatory(
+                split_unescaped(value, ',')
+                    .into_iter()
+                    .map(key_from_name)
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            (1, Some(value)) => SvcParam::Alpn(
+                split_unescaped(value, ',')
+                    .into_iter()
+                    .map(|id| {
+                        let id =
+                            String::from_utf8(unescape(id).context("invalid escape sequence")?)?;
+                        if id.is_empty() || id.len() > 255 {
+                            bail!("invalid alpn id: {id:?}");
+                        }
+                        Ok(id)
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            (2, None) => SvcParam::NoDefaultAlpn,
+            (3, Some(value)) => SvcParam::Port(value.parse()?),
+            (4, Some(value)) => SvcParam::Ipv4Hint(
+                split_unescaped(value, ',')
+                    .into_iter()
+                    .map(Ipv4Addr::from_str)
+                    .collect::<Result<_, _>>()?,
+            ),
+            (5, Some(value)) => SvcParam::Ech(BASE64.decode(value)?),
+            (6, Some(value)) => SvcParam::Ipv6Hint(
+                split_unescaped(value, ',')
+                    .into_iter()
+                    .map(Ipv6Addr::from_str)
+                    .collect::<Result<_, _>>()?,
+            ),
+            (0..=6, _) => bail!("invalid value for service parameter {name}"),
+            (key, value) => SvcParam::Unknown {
+                key,
+                value: unescape(value.unwrap_or_default()).context("invalid escape sequence")?,
+            },
+        };
+
+        Ok(param)
+    }
+}
+
+impl Serialize for SvcParam {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for SvcParam {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct SvcParamVisitor;
+
+        impl Visitor<'_> for SvcParamVisitor {
+            type Value = SvcParam;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a service parameter (e.g., alpn=h2)")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                SvcParam::from_str(v).map_err(|e| E::custom(format!("{e:#}")))
+            }
+        }
+
+        deserializer.deserialize_str(SvcParamVisitor)
+    }
+}
+
+/// Checks that a list of parameters is well formed.
+///
+/// Keys must not repeat, and every key listed as mandatory must be present.
+pub(crate) fn validate(params: &[SvcParam]) -> anyhow::Result<()> {
+    for (i, param) in params.iter().enumerate() {
+        if params[..i].iter().any(|p| p.key() == param.key()) {
+            bail!("duplicate service parameter: {}", key_name(param.key()));
+        }
+    }
+
+    for param in params {
+        if let SvcParam::Mandatory(keys) = param {
+            for key in keys {
+                if *key == 0 || !params.iter().any(|p| p.key() == *key) {
+                    bail!("mandatory service parameter is missing: {}", key_name(*key));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{Bytes, SvcParam};
+
+    #[test]
+    fn svc_param_round_trip() {
+        let inputs = [
+            "mandatory=alpn,port",
+            "alpn=h2,h3",
+            "no-default-alpn",
+            "port=8443",
+            "ipv4hint=192.0.2.1,192.0.2.2",
+            "ech=AEX+DQBB",
+            "ipv6hint=2001:db8::1",
+            "key65333=ex\\,ample",
+        ];
+
+        for input in inputs {
+            let param = SvcParam::from_str(input).unwrap();
+            assert_eq!(param.to_string(), input);
+
+            let mut bytes = Bytes::new();
+            param.to_bytes(&mut bytes);
+            let mut bytes = Bytes::from_buf(bytes.used());
+            assert_eq!(SvcParam::from_bytes(&mut bytes).unwrap(), param);
+        }
+    }
+}
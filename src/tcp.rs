@@ -1,43 +1,83 @@
 use std::{
-    io::{Read, Write},
-    net::TcpStream,
+    io::{ErrorKind, Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Duration,
 };
 
-use crate::{Bytes, Message};
+use crate::{
+    udp::{resolve, TIMEOUT},
+    Bytes, Message, Transport, TransportError,
+};
 
 /// Message transport over TCP.
 pub struct TcpTransport {
     nameserver: String,
+    timeout: Duration,
 }
 
 impl TcpTransport {
     /// Creates a new TcpTransport object.
     pub fn new(nameserver: String) -> Self {
-        Self { nameserver }
+        Self {
+            nameserver,
+            timeout: TIMEOUT,
+        }
     }
 
-    /// Sends a DNS request.
-    pub fn send(&self, request: Message) -> Message {
-        let mut socket = if self.nameserver.contains(":") {
-            TcpStream::connect(&self.nameserver).unwrap()
-        } else {
-            TcpStream::connect((self.nameserver.as_str(), 53)).unwrap()
-        };
+    /// Sets the time to wait for the connection to be established and for
+    /// each read and write (5 seconds by default).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
 
+    /// Sends a request to the nameserver at the given address and reads the
+    /// response.
+    fn exchange(&self, addr: SocketAddr, mut request: Message) -> Result<Message, TransportError> {
+        let mut socket = TcpStream::connect_timeout(&addr, self.timeout)?;
+        socket.set_read_timeout(Some(self.timeout))?;
+        socket.set_write_timeout(Some(self.timeout))?;
+
+        request.header.id = rand::random();
         let mut request_bytes = Bytes::new();
         request.to_bytes(&mut request_bytes);
         let request_len = &(request_bytes.used().len() as u16).to_be_bytes();
-        socket.write(request_len).unwrap();
-        socket.write(request_bytes.used()).unwrap();
+        socket.write_all(request_len)?;
+        socket.write_all(request_bytes.used())?;
 
         let mut response_len_buf = [0; 2];
-        socket.read_exact(&mut response_len_buf).unwrap();
+        socket.read_exact(&mut response_len_buf)?;
         let response_len = u16::from_be_bytes(response_len_buf);
         let mut response_buf = vec![0; response_len as usize];
-        socket.read_exact(&mut response_buf).unwrap();
+        socket.read_exact(&mut response_buf)?;
         let mut response_bytes = Bytes::from_buf(&response_buf);
-        let response = Message::from_bytes(&mut response_bytes);
+        let response = Message::from_bytes(&mut response_bytes)?;
+
+        if !response.is_response_to(&request) {
+            return Err(TransportError::Mismatch { addr });
+        }
 
-        response
+        Ok(response)
+    }
+}
+
+impl Transport for TcpTransport {
+    /// Sends a DNS request.
+    ///
+    /// The request is sent with a random ID.
+    ///
+    /// Returns an error if the nameserver cannot be reached or does not
+    /// respond in time, or if the response is malformed or is not a response
+    /// to the request.
+    fn send(&self, request: Message) -> Result<Message, TransportError> {
+        let addr = resolve(&self.nameserver)?;
+        match self.exchange(addr, request) {
+            Err(TransportError::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                Err(TransportError::Timeout { addr })
+            }
+            response => response,
+        }
     }
 }
@@ -0,0 +1,212 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use crate::{DecodeError, Message};
+
+/// A way of sending DNS requests to a nameserver.
+pub trait Transport {
+    /// Sends a DNS request and returns the response.
+    fn send(&self, request: Message) -> Result<Message, TransportError>;
+}
+
+impl<T: Transport + ?Sized> Transport for &T {
+    fn send(&self, request: Message) -> Result<Message, TransportError> {
+        (**self).send(request)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&self, request: Message) -> Result<Message, TransportError> {
+        (**self).send(request)
+    }
+}
+
+/// An error that occurred while sending a request or receiving a response.
+#[derive(Debug)]
+pub enum TransportError {
+    /// The address of the nameserver could not be resolved.
+    Resolve {
+        nameserver: String,
+        source: Option<io::Error>,
+    },
+    /// An I/O error occurred while communicating with the nameserver.
+    Io(io::Error),
+    /// The nameserver did not respond in time.
+    Timeout { addr: SocketAddr },
+    /// The response could not be decoded.
+    Decode(DecodeError),
+    /// The nameserver sent a response that does not match the request.
+    Mismatch { addr: SocketAddr },
+}
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use TransportError::*;
+
+        match self {
+            Resolve {
+                nameserver,
+                source: Some(_),
+            } => write!(f, "failed to resolve nameserver {nameserver}"),
+            Resolve {
+                nameserver,
+                source: None,
+            } => write!(f, "nameserver {nameserver} has no addresses"),
+            Io(_) => write!(f, "failed to communicate with nameserver"),
+            Timeout { addr } => write!(f, "timed out waiting for a response from {addr}"),
+            Decode(_) => write!(f, "failed to decode response"),
+            Mismatch { addr } => {
+                write!(f, "{addr} sent a response that does not match the request")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TransportError::Resolve {
+                source: Some(e), ..
+            } => Some(e),
+            TransportError::Io(e) => Some(e),
+            TransportError::Decode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TransportError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<DecodeError> for TransportError {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+/// Transport that retries a request over another transport if the response
+/// is truncated, e.g. UDP with fallback to TCP.
+pub struct FallbackTransport<P, F> {
+    pub(crate) primary: P,
+    pub(crate) fallback: F,
+}
+
+impl<P: Transport, F: Transport> FallbackTransport<P, F> {
+    /// Creates a new FallbackTransport object.
+    pub fn new(primary: P, fallback: F) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+impl<P: Transport, F: Transport> Transport for FallbackTransport<P, F> {
+    fn send(&self, request: Message) -> Result<Message, TransportError> {
+        match self.primary.send(request.clone())? {
+            response if response.header.is_truncated => self.fallback.send(request),
+            response => Ok(response),
+        }
+    }
+}
+
+/// In-memory transport that returns scripted responses, for testing code
+/// that sends requests.
+///
+/// Clones share the same script and the same log of requests, so a clone can
+/// be handed to the code under test and the original inspected afterwards.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    responses: VecDeque<Result<Message, TransportError>>,
+    requests: Vec<Message>,
+}
+
+impl MockTransport {
+    /// Creates a new MockTransport object with no scripted responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a response to be returned by a later request.
+    ///
+    /// The ID of the response is set to the ID of the request it answers.
+    pub fn push_response(&self, response: Message) {
+        self.state.lock().unwrap().responses.push_back(Ok(response));
+    }
+
+    /// Adds an error to be returned by a later request.
+    pub fn push_error(&self, error: TransportError) {
+        self.state.lock().unwrap().responses.push_back(Err(error));
+    }
+
+    /// Returns the requests that have been sent, in order.
+    pub fn requests(&self) -> Vec<Message> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl Transport for MockTransport {
+    /// Returns the next scripted response.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no scripted responses left.
+    fn send(&self, request: Message) -> Result<Message, TransportError> {
+        let mut state = self.state.lock().unwrap();
+        let id = request.header.id;
+        state.requests.push(request);
+        let mut response = state
+            .responses
+            .pop_front()
+            .expect("no scripted response left for request")?;
+        response.header.id = id;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, net::SocketAddr};
+
+    use crate::{FallbackTransport, Message, MockTransport, Transport, TransportError};
+
+    #[test]
+    fn fallback_on_truncation() {
+        let udp = MockTransport::new();
+        let tcp = MockTransport::new();
+        let transport = FallbackTransport::new(udp.clone(), tcp.clone());
+
+        let mut truncated = Message::new();
+        truncated.header.is_truncated = true;
+        udp.push_response(truncated);
+        tcp.push_response(Message::new());
+        let response = transport.send(Message::new()).unwrap();
+        assert!(!response.header.is_truncated);
+        assert_eq!(tcp.requests().len(), 1);
+
+        udp.push_response(Message::new());
+        transport.send(Message::new()).unwrap();
+        assert_eq!(udp.requests().len(), 2);
+        assert_eq!(tcp.requests().len(), 1);
+
+        let addr = SocketAddr::from(([192, 0, 2, 1], 53));
+        udp.push_error(TransportError::Timeout { addr });
+        let err = transport.send(Message::new()).unwrap_err();
+        assert!(matches!(err, TransportError::Timeout { .. }));
+        assert_eq!(tcp.requests().len(), 1);
+
+        tcp.push_error(io::Error::from(io::ErrorKind::ConnectionRefused).into());
+        let err = tcp.send(Message::new()).unwrap_err();
+        assert!(matches!(err, TransportError::Io(_)));
+    }
+}
@@ -1,9 +1,25 @@
-use crate::{Bytes, Message};
+use std::{
+    io::{self, ErrorKind},
+    net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use rand::Rng;
+
+use crate::{Bytes, Message, Transport, TransportError};
+
+/// Default time to wait for a response before giving up.
+pub(crate) const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of random source ports to try before letting the operating system
+/// choose one.
+const BIND_ATTEMPTS: usize = 8;
 
 /// Message transport over UDP.
 pub struct UdpTransport {
     nameserver: String,
     max_response_size: u16,
+    timeout: Duration,
 }
 
 impl UdpTransport {
@@ -12,31 +28,183 @@ impl UdpTransport {
         Self {
             nameserver,
             max_response_size: max_size,
+            timeout: TIMEOUT,
         }
     }
 
+    /// Sets the time to wait for a response (5 seconds by default).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl Transport for UdpTransport {
     /// Sends a DNS request.
-    pub fn send(&self, request: Message) -> Message {
-        let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+    ///
+    /// The request is sent with a random ID from a random source port.
+    /// Datagrams that are not a response to the request (i.e., that come
+    /// from another address, have another ID or do not echo the question)
+    /// are discarded, so that off-path attackers cannot easily spoof a
+    /// response.
+    ///
+    /// Returns an error if the nameserver cannot be reached or does not
+    /// respond in time.
+    fn send(&self, mut request: Message) -> Result<Message, TransportError> {
+        let addr = resolve(&self.nameserver)?;
+        let socket = bind_random_port(addr)?;
+        socket.connect(addr)?;
 
+        request.header.id = rand::random();
         let mut request_bytes = Bytes::new();
         request.to_bytes(&mut request_bytes);
+        socket.send(request_bytes.used())?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut response_buf = vec![0; self.max_response_size as usize];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(TransportError::Timeout { addr });
+            }
+            socket.set_read_timeout(Some(remaining))?;
 
-        if self.nameserver.contains(":") {
-            socket
-                .send_to(request_bytes.used(), &self.nameserver)
-                .unwrap();
-        } else {
-            socket
-                .send_to(request_bytes.used(), (self.nameserver.as_str(), 53))
-                .unwrap();
+            let len = match socket.recv(&mut response_buf) {
+                Ok(len) => len,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            // the socket is connected, so datagrams from other addresses are
+            // filtered out by the operating system
+            let mut response_bytes = Bytes::from_buf(&response_buf[..len]);
+            match Message::from_bytes(&mut response_bytes) {
+                Ok(response) if response.is_response_to(&request) => return Ok(response),
+                _ => continue,
+            }
         }
+    }
+}
 
-        let mut response_buf = vec![0; self.max_response_size as usize];
-        let (_, _) = socket.recv_from(&mut response_buf).unwrap();
-        let mut response_bytes = Bytes::from_buf(&response_buf);
-        let response = Message::from_bytes(&mut response_bytes);
+/// Resolves the address of a nameserver, which may include a port (53 by
+/// default).
+pub(crate) fn resolve(nameserver: &str) -> Result<SocketAddr, TransportError> {
+    if let Ok(ip) = nameserver.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, 53));
+    }
+
+    let addrs = if nameserver.contains(':') {
+        nameserver.to_socket_addrs()
+    } else {
+        (nameserver, 53).to_socket_addrs()
+    };
+
+    addrs
+        .map_err(|e| TransportError::Resolve {
+            nameserver: nameserver.to_owned(),
+            source: Some(e),
+        })?
+        .next()
+        .ok_or_else(|| TransportError::Resolve {
+            nameserver: nameserver.to_owned(),
+            source: None,
+        })
+}
+
+/// Binds a UDP socket to a random port, in the address family of the
+/// nameserver.
+pub(crate) fn bind_random_port(nameserver: SocketAddr) -> io::Result<UdpSocket> {
+    let mut local = match nameserver {
+        SocketAddr::V4(_) => SocketAddr::from(([0, 0, 0, 0], 0)),
+        SocketAddr::V6(_) => SocketAddr::from(([0; 16], 0)),
+    };
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..BIND_ATTEMPTS {
+        local.set_port(rng.gen_range(1024..=u16::MAX));
+        if let Ok(socket) = UdpSocket::bind(local) {
+            return Ok(socket);
+        }
+    }
+
+    local.set_port(0);
+    UdpSocket::bind(local)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::UdpSocket,
+        str::FromStr,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        Bytes, Message, Name, Question, QuestionClass, QuestionType, ResponseCode, Transport,
+        TransportError, UdpTransport,
+    };
+
+    #[test]
+    fn silent_nameserver_times_out() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let nameserver = server.local_addr().unwrap().to_string();
+
+        let transport = UdpTransport::new(nameserver, 512).with_timeout(Duration::from_millis(50));
+        let start = Instant::now();
+        let err = transport.send(Message::new()).unwrap_err();
+        assert!(matches!(err, TransportError::Timeout { .. }));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn mismatched_responses_are_discarded() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let nameserver = server.local_addr().unwrap().to_string();
+
+        let mut request = Message::new();
+        request.header.question_count = 1;
+        request.questions = vec![Question {
+            name: Name::from_str("example.com.").unwrap(),
+            q_type: QuestionType::A,
+            q_class: QuestionClass::In,
+        }];
+
+        let handle = thread::spawn(move || {
+            let mut buf = [0; 512];
+            let (len, client) = server.recv_from(&mut buf).unwrap();
+            let query = Message::from_bytes(&mut Bytes::from_buf(&buf[..len])).unwrap();
+
+            let send = |response: &Message| {
+                let mut bytes = Bytes::new();
+                response.to_bytes(&mut bytes);
+                server.send_to(bytes.used(), client).unwrap();
+            };
+
+            let mut response = query.clone();
+            response.header.is_response = true;
+
+            // a spoofed response with the wrong ID, one for another question
+            // and finally the real response
+            let mut spoofed = response.clone();
+            spoofed.header.id = query.header.id.wrapping_add(1);
+            spoofed.header.resp_code = ResponseCode::NameError;
+            send(&spoofed);
+
+            let mut other = spoofed.clone();
+            other.header.id = query.header.id;
+            other.questions[0].name = Name::from_str("example.net.").unwrap();
+            send(&other);
+
+            server.send_to(b"garbage", client).unwrap();
+            send(&response);
+        });
 
-        response
+        let transport = UdpTransport::new(nameserver, 512);
+        let response = transport.send(request).unwrap();
+        assert_eq!(response.header.resp_code, ResponseCode::Success);
+        handle.join().unwrap();
     }
 }